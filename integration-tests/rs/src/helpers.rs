@@ -1,9 +1,10 @@
-use near_units::parse_near;
 use serde_json::json;
 use workspaces::{network::Sandbox, Account, Contract, Worker, AccountDetails};
 
 pub const DEFAULT_DEPOSIT: u128 = 6760000000000000000000 as u128;
 pub const DEFAULT_GAS: u128 = 300000000000000 as u128;
+// mint price and storage of a token with the history reserve, the excess is refunded
+pub const DEFAULT_DEPOSIT_FOR_MINT: u128 = 5100000000000000000000000 as u128;
 
 
 pub async fn mint_nft(
@@ -17,7 +18,7 @@ pub async fn mint_nft(
 
     user.call(&worker, nft_contract.id(), "nft_mint")
         .args_json(request_payload)?
-        .deposit(parse_near!("5.008 N"))
+        .deposit(DEFAULT_DEPOSIT_FOR_MINT)
         .transact()
         .await?;
     
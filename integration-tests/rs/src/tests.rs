@@ -50,7 +50,7 @@ async fn main() -> anyhow::Result<()> {
     // Initialize contracts
     nft_contract
         .call(&worker, "new_default_meta")
-        .args_json(serde_json::json!({"owner_id": owner.id(), "treasury_id": treasury.id(), "max_supply": "9", "base_uri": DEFAULT_BASE_URI, "mint_price": "5000000000000000000000000","mint_start": "1", "mint_end": "1690000000000000000", "perpetual_royalties": {treasury.id().to_string(): 2000}}))?
+        .args_json(serde_json::json!({"owner_id": owner.id(), "treasury_id": treasury.id(), "max_supply": "9", "base_uri": DEFAULT_BASE_URI, "mint_price": "5000000000000000000000000","mint_start": "1", "mint_end": "4102444800000000000", "perpetual_royalties": {treasury.id().to_string(): 2000}}))?
         .transact()
         .await?;
     market_contract
@@ -68,6 +68,7 @@ async fn main() -> anyhow::Result<()> {
     test_transfer_nft_when_listed_on_marketplace(&alice, &bob, &charlie, &nft_contract, &market_contract, &worker).await?;
     test_approval_revoke(&alice, &bob, &nft_contract, &market_contract, &worker).await?;
     test_reselling_and_royalties(&alice, &bob, &charlie, &nft_contract, &market_contract, &treasury, &worker).await?;
+    test_nft_approve_call_insufficient_storage(&charlie, &nft_contract, &market_contract, &worker).await?;
 
     Ok(())
 }
@@ -116,7 +117,7 @@ async fn test_nft_mint_call(
 
     user.call(&worker, contract.id(), "nft_mint")
         .args_json(request_payload)?
-        .deposit(parse_near!("5.008 N"))
+        .deposit(helpers::DEFAULT_DEPOSIT_FOR_MINT)
         .transact()
         .await?;
 
@@ -199,39 +200,29 @@ async fn test_nft_approve_call_long_msg_string(
         "msg": "sample message".repeat(10240),
     });
 
-    match user.call(&worker, nft_contract.id(), "nft_approve")
+    // the marketplace panics with "Not valid SaleArgs" and the approval is rolled back
+    let approved: bool = user.call(&worker, nft_contract.id(), "nft_approve")
         .args_json(approve_payload)?
         .deposit(helpers::DEFAULT_DEPOSIT)
         .gas(helpers::DEFAULT_GAS as u64)
         .transact()
-        .await
-    {
-        Ok(_result) => {
-            panic!("test_nft_approve_call_long_msg_string worked despite insufficient gas")
-        }
-        Err(e) => {
-            let e_string = e.to_string();
-            if !e_string
-                .contains("Not valid SaleArgs")
-            {
-                panic!("test_nft_approve_call_long_msg_string displays unexpected error message: {:?}", e_string);
-            }
+        .await?
+        .json()?;
+    assert_eq!(approved, false, "nft_resolve_approve kept the approval");
 
-            let view_payload = json!({
-                "token_id": token_id,
-                "approved_account_id": market_contract.id(),
-            });
-            let result: bool = user
-                .call(&worker, nft_contract.id(), "nft_is_approved")
-                .args_json(view_payload)?
-                .transact()
-                .await?
-                .json()?;
-            
-            assert_eq!(result, true);
-            println!("      Passed ✅ test_nft_approve_call_long_msg_string");
-        }
-    }
+    let view_payload = json!({
+        "token_id": token_id,
+        "approved_account_id": market_contract.id(),
+    });
+    let result: bool = user
+        .call(&worker, nft_contract.id(), "nft_is_approved")
+        .args_json(view_payload)?
+        .transact()
+        .await?
+        .json()?;
+
+    assert_eq!(result, false);
+    println!("      Passed ✅ test_nft_approve_call_long_msg_string");
     Ok(())
 }
 
@@ -366,7 +357,7 @@ async fn test_reselling_and_royalties(
     });
     user.call(&worker, nft_contract.id(), "nft_mint")
         .args_json(request_payload)?
-        .deposit(parse_near!("5.1 N"))
+        .deposit(helpers::DEFAULT_DEPOSIT_FOR_MINT)
         .transact()
         .await?;
 
@@ -417,3 +408,45 @@ async fn test_reselling_and_royalties(
     println!("      Passed ✅ test_reselling_and_royalties");
     Ok(())
 }
+
+async fn test_nft_approve_call_insufficient_storage(
+    user: &Account,
+    nft_contract: &Contract,
+    market_contract: &Contract,
+    worker: &Worker<Sandbox>,
+) -> anyhow::Result<()> {
+    // the next token ID is the number of tokens minted so far
+    let total_supply: String = nft_contract
+        .call(&worker, "nft_total_supply")
+        .args_json(json!({}))?
+        .transact()
+        .await?
+        .json()?;
+    let token_id = total_supply.as_str();
+    let sale_price = 3000000000000000000000000 as u128;  // 3 NEAR in yoctoNEAR
+    helpers::mint_nft(user, nft_contract, worker).await?;
+    let token_info: serde_json::Value = helpers::get_nft_token_info(nft_contract, worker, token_id).await?;
+    assert_eq!(token_info["owner_id"], json!(user.id()), "token was not minted");
+
+    // no storage paid on the marketplace, nft_on_approve panics with "Insufficient storage paid"
+    let approve_payload  = json!({
+        "token_id": token_id,
+        "account_id": market_contract.id(),
+        "msg": format!(r#"{{ "sale_conditions" : "{}" }}"#, sale_price.to_string()),
+    });
+    let approved: bool = user.call(&worker, nft_contract.id(), "nft_approve")
+        .args_json(approve_payload)?
+        .deposit(helpers::DEFAULT_DEPOSIT)
+        .gas(helpers::DEFAULT_GAS as u64)
+        .transact()
+        .await?
+        .json()?;
+    assert_eq!(approved, false, "nft_resolve_approve kept the approval");
+
+    // assert the approval is removed
+    let token_info: serde_json::Value = helpers::get_nft_token_info(nft_contract, worker, token_id).await?;
+    assert_eq!(token_info["approved_account_ids"], json!({}), "approval was not rolled back");
+
+    println!("      Passed ✅ test_nft_approve_call_insufficient_storage");
+    Ok(())
+}
@@ -181,7 +181,34 @@ test("nft contract: nft approve call long msg string", async (t) => {
     approved_account_id: market_contract,
   };
   const approved = await nft_contract.view("nft_is_approved", view_payload);
-  t.true(approved, "NFT approval apss without sale args");
+  t.false(approved, "NFT approval was not rolled back without sale args");
+});
+
+test("nft contract: nft approve call without storage paid", async (t) => {
+  const { alice, nft_contract, market_contract } = t.context.accounts;
+  await mintNFT(alice, nft_contract);
+
+  // approve NFT for sale without paying for the market storage
+  const approve_payload = {
+    token_id: "0",
+    account_id: market_contract,
+    msg: '{"sale_conditions": "300000000000000000000000" }',
+  };
+  const result = await alice.callRaw(
+    nft_contract,
+    "nft_approve",
+    approve_payload,
+    defaultCallOptions()
+  );
+  t.regex(result.receiptFailureMessages.join("\n"), /Insufficient storage paid+/);
+
+  // test if the approval was rolled back
+  const view_payload = {
+    token_id: "0",
+    approved_account_id: market_contract,
+  };
+  const approved = await nft_contract.view("nft_is_approved", view_payload);
+  t.false(approved, "NFT approval was not rolled back without storage paid");
 });
 
 test("cross contract: sell NFT listed on marketplace", async (t) => {
@@ -2,11 +2,11 @@ import { BN, NearAccount } from "near-workspaces";
 
 export const DEFAULT_GAS: string = "30000000000000";
 export const DEFAULT_DEPOSIT: string = "9050000000000000000000";
-export const DEFAULT_DEPOSIT_FOR_MINT: string = "5009050000000000000000000";
+export const DEFAULT_DEPOSIT_FOR_MINT: string = "5100000000000000000000000"; // price and storage with the history reserve
 export const DEFAULT_BASE_URI: string = "https://gateway.purecube.io/chubby-runners-testnet";
 export const MINT_PRICE: string = "5000000000000000000000000";
 export const MINT_START: string = "1600000000000000000";
-export const MINT_END: string = "1690000000000000000"; // Sat Jul 22 2023 04:26:40
+export const MINT_END: string = "4102444800000000000"; // Fri Jan 01 2100 00:00:00
 
 export async function purchaseListedNFT(
   nft_contract: NearAccount,
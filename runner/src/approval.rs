@@ -1,9 +1,11 @@
 use crate::*;
-use near_sdk::{ext_contract};
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+const GAS_FOR_RESOLVE_APPROVE: Gas = Gas(5_000_000_000_000);
 
 pub trait NonFungibleTokenCore {
-    //approve an account ID to transfer a token on your behalf
-    fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>);
+    //approve an account ID to transfer a token on your behalf. The gas is attached to nft_on_approve if passed in
+    fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>, gas: Option<U64>);
 
     //check if the passed in account has access to approve the token ID
 	fn nft_is_approved(
@@ -20,6 +22,15 @@ pub trait NonFungibleTokenCore {
     fn nft_revoke_all(&mut self, token_id: TokenId);
 }
 
+//approvals of the token before nft_transfer_call, restored if the receiver returns the token
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PreviousApprovals {
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    //expirations of the approvals by approval ID
+    pub expirations: HashMap<u64, u64>,
+}
+
 #[ext_contract(ext_non_fungible_approval_receiver)]
 trait NonFungibleTokenApprovalsReceiver {
     //cross contract call to an external contract that is initiated during nft_approve
@@ -32,70 +43,33 @@ trait NonFungibleTokenApprovalsReceiver {
     );
 }
 
+/*
+    resolves the promise of the cross contract call to the approved account
+    this is stored on THIS contract and removes the approval if nft_on_approve failed
+*/
+pub trait NonFungibleTokenApprovalResolver {
+    fn nft_resolve_approve(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        account_id: AccountId,
+        approval_id: u64,
+    ) -> bool;
+}
+
 #[near_bindgen]
 impl NonFungibleTokenCore for Contract {
 
     //allow a specific account ID to approve a token on your behalf
     #[payable]
-    fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>) {
-        /*
-            assert at least one yocto for security reasons - this will cause a redirect to the NEAR wallet.
-            The user needs to attach enough to pay for storage on the contract
-        */
-        assert_at_least_one_yocto();
-
-        //get the token object from the token ID
-        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
-
-        //make sure that the person calling the function is the owner of the token
-        assert_eq!(
-            &env::predecessor_account_id(),
-            &token.owner_id,
-            "Predecessor must be the token owner."
-        );
-
-        //get the next approval ID if we need a new approval
-        let approval_id: u64 = token.next_approval_id;
-
-        //check if the account has been approved already for this token
-        let is_new_approval = token
-            .approved_account_ids
-            //insert returns none if the key was not present.  
-            .insert(account_id.clone(), approval_id)
-            //if the key was not present, .is_none() will return true so it is a new approval.
-            .is_none();
-
-        //if it was a new approval, we need to calculate how much storage is being used to add the account.
-        let storage_used = if is_new_approval {
-            bytes_for_approved_account_id(&account_id)
-        //if it was not a new approval, we used no storage.
-        } else {
-            0
-        };
-
-        //increment the token's next approval ID by 1
-        token.next_approval_id += 1;
-        //insert the token back into the tokens_by_id collection
-        self.tokens_by_id.insert(&token_id, &token);
-
-        //refund any excess storage attached by the user. If the user didn't attach enough, panic. 
-        refund_deposit(storage_used);
-
-        //if some message was passed into the function, we initiate a cross contract call on the
-        //account we're giving access to. 
-        if let Some(msg) = msg {
-            // Defaulting GAS weight to 1, no attached deposit, and no static GAS to attach.
-            ext_non_fungible_approval_receiver::ext(account_id)
-                .nft_on_approve(
-                    token_id, 
-                    token.owner_id, 
-                    approval_id, 
-                    msg
-                ).as_return();
-        }
+    fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>, gas: Option<U64>) {
+        self.internal_approve(token_id, account_id, None, msg, gas);
     }
 
-    //check if the passed in account has access to approve the token ID
+    /*
+        check if the passed in account has access to approve the token ID. This is a view so an expired approval
+        is only reported as not approved, nft_remove_expired_approvals removes it and refunds its storage
+    */
 	fn nft_is_approved(
         &self,
         token_id: TokenId,
@@ -110,6 +84,11 @@ impl NonFungibleTokenCore for Contract {
 
         //if there was some approval ID found for the account ID
         if let Some(approval) = approval {
+            //an expired approval doesn't give access anymore
+            if !self.internal_is_approval_active(&token_id, *approval) {
+                return false;
+            }
+
             //if a specific approval_id was passed into the function
 			if let Some(approval_id) = approval_id {
                 //return if the approval ID passed in matches the actual approval ID for the account
@@ -148,6 +127,9 @@ impl NonFungibleTokenCore for Contract {
             //insert the token back into the tokens_by_id collection with the account_id removed from the approval list
             self.tokens_by_id.insert(&token_id, &token);
         }
+
+        //drop the expiration of the revoked approval and the expired approvals with theirs
+        self.internal_purge_expired_approvals(&token_id);
     }
 
     //revoke all accounts from transferring the token on your behalf
@@ -171,5 +153,277 @@ impl NonFungibleTokenCore for Contract {
             //insert the token back into the tokens_by_id collection with the approved account IDs cleared
             self.tokens_by_id.insert(&token_id, &token);
         }
+
+        //drop the expirations of the revoked approvals
+        self.internal_prune_approval_expirations(&token_id, &token.owner_id);
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenApprovalResolver for Contract {
+    //resolves the cross contract call when calling nft_on_approve in the nft_approve method
+    //returns true if the approval was kept. A failed call removes the approval and refunds its storage to the owner
+    #[private]
+    fn nft_resolve_approve(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        account_id: AccountId,
+        approval_id: u64,
+    ) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        //the approval could be changed or the token transferred in the meantime
+        let mut token = if let Some(token) = self.tokens_by_id.get(&token_id) {
+            token
+        } else {
+            return false;
+        };
+        if token.owner_id != owner_id || token.approved_account_ids.get(&account_id) != Some(&approval_id) {
+            return false;
+        }
+
+        //remove the approval and refund the owner for the storage used up by it
+        token.approved_account_ids.remove(&account_id);
+        self.tokens_by_id.insert(&token_id, &token);
+        refund_approved_account_ids_iter(owner_id.clone(), [account_id].iter());
+
+        //and by its expiration, the expired approvals go too
+        self.internal_purge_expired_approvals(&token_id);
+
+        false
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //approve an account ID to transfer a token on your behalf until expires_at (nanoseconds)
+    #[payable]
+    pub fn nft_approve_with_expiry(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        expires_at: U64,
+        msg: Option<String>,
+        gas: Option<U64>,
+    ) {
+        self.internal_approve(token_id, account_id, Some(expires_at.0), msg, gas);
+    }
+
+    //get when the approval of the account expires, nothing if it never expires or there is no approval
+    pub fn nft_approval_expires_at(&self, token_id: TokenId, account_id: AccountId) -> Option<U64> {
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        token
+            .approved_account_ids
+            .get(&account_id)
+            .and_then(|approval_id| self.internal_approval_expires_at(&token_id, *approval_id))
+            .map(U64)
+    }
+
+    //remove the expired approvals of the token, anyone can call it. The owner gets back the storage they used up
+    pub fn nft_remove_expired_approvals(&mut self, token_id: TokenId) {
+        assert!(self.tokens_by_id.get(&token_id).is_some(), "No token");
+        self.internal_purge_expired_approvals(&token_id);
+    }
+}
+
+impl Contract {
+    //approve an account ID to transfer the token on behalf of the owner until the approval expires (nanoseconds)
+    pub(crate) fn internal_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        expires_at: Option<u64>,
+        msg: Option<String>,
+        gas: Option<U64>,
+    ) {
+        /*
+            assert at least one yocto for security reasons - this will cause a redirect to the NEAR wallet.
+            The user needs to attach enough to pay for storage on the contract
+        */
+        assert_at_least_one_yocto();
+
+        //get the token object from the token ID
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+
+        //make sure that the person calling the function is the owner of the token
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &token.owner_id,
+            "Predecessor must be the token owner."
+        );
+
+        //expired approvals are removed lazily whenever the owner touches the approvals
+        self.internal_remove_expired_approvals(&token_id, &mut token);
+
+        //get the next approval ID if we need a new approval
+        let approval_id: u64 = token.next_approval_id;
+
+        //check if the account has been approved already for this token
+        let is_new_approval = token
+            .approved_account_ids
+            //insert returns none if the key was not present.  
+            .insert(account_id.clone(), approval_id)
+            //if the key was not present, .is_none() will return true so it is a new approval.
+            .is_none();
+
+        //if it was a new approval, we need to calculate how much storage is being used to add the account.
+        let mut storage_used = if is_new_approval {
+            bytes_for_approved_account_id(&account_id)
+        //if it was not a new approval, we used no storage.
+        } else {
+            0
+        };
+
+        //increment the token's next approval ID by 1
+        token.next_approval_id += 1;
+        //insert the token back into the tokens_by_id collection
+        self.tokens_by_id.insert(&token_id, &token);
+
+        //the expiration of a replaced approval isn't needed anymore
+        self.internal_prune_approval_expirations(&token_id, &token.owner_id);
+
+        //keep track of when the approval expires
+        if let Some(expires_at) = expires_at {
+            assert!(
+                expires_at > env::block_timestamp(),
+                "Expires {} must be in the future, now {}",
+                expires_at,
+                env::block_timestamp()
+            );
+
+            let initial_storage_usage = env::storage_usage();
+            let mut expirations = self.approval_expirations.get(&token_id).unwrap_or_default();
+            expirations.insert(approval_id, expires_at);
+            self.approval_expirations.insert(&token_id, &expirations);
+            storage_used += env::storage_usage() - initial_storage_usage;
+        }
+
+        //refund any excess storage attached by the user. If the user didn't attach enough, panic. 
+        refund_deposit(storage_used);
+
+        //if some message was passed into the function, we initiate a cross contract call on the
+        //account we're giving access to. The approval is removed if the call fails.
+        if let Some(msg) = msg {
+            // Defaulting GAS weight to 1, no attached deposit, and the passed in static GAS if any.
+            ext_non_fungible_approval_receiver::ext(account_id.clone())
+                .with_static_gas(gas.map(|gas| Gas(gas.0)).unwrap_or_default())
+                .nft_on_approve(
+                    token_id.clone(), 
+                    token.owner_id.clone(), 
+                    approval_id, 
+                    msg
+                )
+            // We then resolve the promise and call nft_resolve_approve on our own contract
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_APPROVE)
+                    .nft_resolve_approve(
+                        token.owner_id,
+                        token_id,
+                        account_id,
+                        approval_id,
+                    )
+            ).as_return();
+        }
+    }
+
+    //get when the approval expires (nanoseconds), nothing if it never expires
+    pub(crate) fn internal_approval_expires_at(&self, token_id: &TokenId, approval_id: u64) -> Option<u64> {
+        self.approval_expirations
+            .get(token_id)
+            .and_then(|expirations| expirations.get(&approval_id).copied())
+    }
+
+    //check if the approval didn't expire yet
+    pub(crate) fn internal_is_approval_active(&self, token_id: &TokenId, approval_id: u64) -> bool {
+        self.internal_approval_expires_at(token_id, approval_id)
+            .map_or(true, |expires_at| expires_at > env::block_timestamp())
+    }
+
+    //remove the expired approvals from the token and refund the owner for the storage they used up
+    fn internal_remove_expired_approvals(&self, token_id: &TokenId, token: &mut Token) {
+        let expired: Vec<AccountId> = token
+            .approved_account_ids
+            .iter()
+            .filter(|(_, approval_id)| !self.internal_is_approval_active(token_id, **approval_id))
+            .map(|(account_id, _)| account_id.clone())
+            .collect();
+
+        if !expired.is_empty() {
+            for account_id in expired.iter() {
+                token.approved_account_ids.remove(account_id);
+            }
+            refund_approved_account_ids_iter(token.owner_id.clone(), expired.iter());
+        }
+    }
+
+    //remove the expired approvals of the token and their expirations and refund the owner for the storage they used up
+    pub(crate) fn internal_purge_expired_approvals(&mut self, token_id: &TokenId) {
+        let mut token = if let Some(token) = self.tokens_by_id.get(token_id) {
+            token
+        } else {
+            return;
+        };
+
+        let approvals = token.approved_account_ids.len();
+        self.internal_remove_expired_approvals(token_id, &mut token);
+        if token.approved_account_ids.len() < approvals {
+            self.tokens_by_id.insert(token_id, &token);
+        }
+        self.internal_prune_approval_expirations(token_id, &token.owner_id);
     }
-}
\ No newline at end of file
+
+    //drop the expirations of the approvals the token doesn't have anymore and refund the storage to the passed in account ID
+    pub(crate) fn internal_prune_approval_expirations(&mut self, token_id: &TokenId, refund_to: &AccountId) {
+        let mut expirations = if let Some(expirations) = self.approval_expirations.get(token_id) {
+            expirations
+        } else {
+            return;
+        };
+
+        let initial_storage_usage = env::storage_usage();
+
+        let approval_ids: Vec<u64> = self
+            .tokens_by_id
+            .get(token_id)
+            .map(|token| token.approved_account_ids.into_values().collect())
+            .unwrap_or_default();
+        expirations.retain(|approval_id, _| approval_ids.contains(approval_id));
+
+        if expirations.is_empty() {
+            self.approval_expirations.remove(token_id);
+        } else {
+            self.approval_expirations.insert(token_id, &expirations);
+        }
+
+        let final_storage_usage = env::storage_usage();
+        if final_storage_usage < initial_storage_usage {
+            refund_released_storage(refund_to.clone(), initial_storage_usage - final_storage_usage);
+        }
+    }
+
+    //put back the expirations kept aside during nft_transfer_call
+    pub(crate) fn internal_restore_approval_expirations(&mut self, token_id: &TokenId, approval_expirations: HashMap<u64, u64>) {
+        if approval_expirations.is_empty() {
+            return;
+        }
+        let mut expirations = self.approval_expirations.get(token_id).unwrap_or_default();
+        expirations.extend(approval_expirations);
+        self.approval_expirations.insert(token_id, &expirations);
+    }
+
+    //refund the storage of the expirations kept aside during nft_transfer_call once they are not needed anymore
+    pub(crate) fn internal_refund_approval_expirations(
+        &mut self,
+        token_id: &TokenId,
+        approval_expirations: HashMap<u64, u64>,
+        refund_to: &AccountId,
+    ) {
+        //the previous approvals aren't on the token anymore so pruning measures and refunds their storage
+        self.internal_restore_approval_expirations(token_id, approval_expirations);
+        self.internal_prune_approval_expirations(token_id, refund_to);
+    }
+}
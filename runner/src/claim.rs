@@ -0,0 +1,152 @@
+use crate::*;
+
+//max number of claim codes registered or revoked by one call (GAS limits this)
+const MAX_CLAIM_CODES: usize = 100;
+
+//a registered claim code
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ClaimCode {
+    //nanoseconds timestamp when the code stops working
+    pub expires_at: Option<u64>,
+    //yoctoNEAR reserved to pay for the storage of the token minted by the claim
+    pub mint_deposit: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        register the sha256 hashes of secret claim codes, every code valid until its expiry (nanoseconds) if passed in.
+        the owner pays for the storage of the codes and reserves the storage of a token with the traits
+        of the trait table for every code, so the tokens can be claimed by accounts without any NEAR
+    */
+    #[payable]
+    pub fn register_claim_codes(&mut self, codes: Vec<(Base64VecU8, Option<U64>)>) {
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        assert!(!codes.is_empty(), "Nothing to register");
+        assert!(
+            codes.len() <= MAX_CLAIM_CODES,
+            "Cannot register more than {} claim codes at once",
+            MAX_CLAIM_CODES
+        );
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let mint_deposit = self.internal_mint_storage_deposit();
+        for (code_hash, expires_at) in codes.iter() {
+            if let Some(expires_at) = expires_at {
+                assert!(
+                    expires_at.0 > env::block_timestamp(),
+                    "Expires {} must be in the future, now {}",
+                    expires_at.0,
+                    env::block_timestamp()
+                );
+            }
+            let claim_code = ClaimCode {
+                expires_at: expires_at.map(|expires_at| expires_at.0),
+                mint_deposit,
+            };
+            assert!(
+                self.claim_codes.insert(&claim_code_hash(code_hash), &claim_code).is_none(),
+                "Claim code is already registered"
+            );
+        }
+        self.claim_codes_count += codes.len() as u64;
+
+        //the owner pays for the storage of the codes and of the tokens they mint
+        let required_cost = env::storage_byte_cost() * Balance::from(env::storage_usage() - initial_storage_usage)
+            + mint_deposit * codes.len() as Balance;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            required_cost <= attached_deposit,
+            "Must attach {} yoctoNEAR to cover storage",
+            required_cost,
+        );
+
+        //if the refund is greater than 1 yocto NEAR, we refund the predecessor that amount
+        let refund = attached_deposit - required_cost;
+        if refund > 1 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+
+    //revoke unclaimed claim codes, expired or not. The owner gets back the storage and the reserved mint deposits
+    #[payable]
+    pub fn revoke_claim_codes(&mut self, code_hashes: Vec<Base64VecU8>) {
+        assert_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        assert!(
+            code_hashes.len() <= MAX_CLAIM_CODES,
+            "Cannot revoke more than {} claim codes at once",
+            MAX_CLAIM_CODES
+        );
+
+        let initial_storage_usage = env::storage_usage();
+
+        let mut revoked: u64 = 0;
+        let mut refund: Balance = 0;
+        for code_hash in code_hashes.iter() {
+            if let Some(claim_code) = self.claim_codes.remove(&claim_code_hash(code_hash)) {
+                revoked += 1;
+                refund += claim_code.mint_deposit;
+            }
+        }
+        self.claim_codes_count -= revoked;
+
+        refund += env::storage_byte_cost() * Balance::from(initial_storage_usage - env::storage_usage());
+        if refund > 0 {
+            Promise::new(self.owner_id.clone()).transfer(refund);
+        }
+    }
+
+    /*
+        mint a token for the receiver with the secret of a registered claim code. Every code can be claimed once and the
+        owner gets back the storage of the code. Named nft_claim because claim pays the staking rewards
+    */
+    pub fn nft_claim(&mut self, secret: String, receiver_id: AccountId) -> TokenId {
+        let code_hash: CryptoHash = env::sha256(secret.as_bytes()).try_into().unwrap();
+        let claim_code = self.claim_codes.get(&code_hash).expect("Invalid claim code");
+        if let Some(expires_at) = claim_code.expires_at {
+            assert!(
+                expires_at > env::block_timestamp(),
+                "Claim code expired {}, now {}",
+                expires_at,
+                env::block_timestamp()
+            );
+        }
+
+        //the storage of the code goes back to the owner who paid for it
+        let initial_storage_usage = env::storage_usage();
+        self.claim_codes.remove(&code_hash);
+        self.claim_codes_count -= 1;
+        refund_released_storage(self.owner_id.clone(), initial_storage_usage - env::storage_usage());
+
+        //the storage of the token is covered by the deposit reserved with the code
+        self.internal_mint_reserved(receiver_id, env::predecessor_account_id(), claim_code.mint_deposit, self.owner_id.clone())
+    }
+
+    //get the number of claim codes which weren't claimed or revoked yet, expired ones included
+    pub fn claim_codes_remaining(&self) -> U64 {
+        U64(self.claim_codes_count)
+    }
+
+    //check if the claim code with the sha256 hash can be claimed right now
+    pub fn claim_code_valid(&self, code_hash: Base64VecU8) -> bool {
+        self.claim_codes
+            .get(&claim_code_hash(&code_hash))
+            .is_some_and(|claim_code| claim_code.expires_at.map_or(true, |expires_at| expires_at > env::block_timestamp()))
+    }
+}
+
+//convert the passed in sha256 hash of a claim code
+fn claim_code_hash(code_hash: &Base64VecU8) -> CryptoHash {
+    code_hash.0.as_slice().try_into().expect("Claim code hash has to be 32 bytes")
+}
@@ -0,0 +1,234 @@
+use crate::*;
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+//max number of keys added or removed by one call (GAS limits this)
+const MAX_DROP_KEYS: usize = 50;
+//yoctoNEAR the ephemeral access key can spend on GAS, the treasury pays it with every drop
+const DROP_KEY_ALLOWANCE: Balance = 100_000_000_000_000_000_000_000;
+//methods the ephemeral access key can call
+const DROP_KEY_METHODS: &str = "claim_drop,create_account_and_claim_drop";
+//yoctoNEAR per GAS the allowance of the key is charged with, the minimum gas price of the network
+const DROP_GAS_PRICE: Balance = 100_000_000;
+const GAS_FOR_CREATE_ACCOUNT: Gas = Gas(40_000_000_000_000);
+const GAS_FOR_RESOLVE_DROP: Gas = Gas(20_000_000_000_000);
+
+//NEAR held by the contract for a drop until it is claimed or removed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftDrop {
+    //yoctoNEAR sent to the account which claims the drop
+    pub account_deposit: U128,
+    //yoctoNEAR of DROP_KEY_ALLOWANCE not spent on the GAS of the claims yet, returned to the treasury
+    pub allowance: U128,
+    //yoctoNEAR reserved to pay for the storage of the token minted by the claim
+    pub mint_deposit: U128,
+}
+
+#[ext_contract(ext_linkdrop)]
+trait Linkdrop {
+    //creates a new top level account with a full access key, implemented by the near and testnet accounts
+    fn create_account(&mut self, new_account_id: AccountId, new_public_key: PublicKey) -> Promise;
+}
+
+/*
+    resolves the promise of the account creation
+    this is stored on THIS contract and mints the token of the drop once the account exists
+*/
+pub trait DropResolver {
+    fn nft_resolve_drop(&mut self, new_account_id: AccountId, public_key: PublicKey, drop: NftDrop) -> bool;
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        fund a drop for every ephemeral public key, modeled on the NEAR linkdrop. Every key is added as a
+        function call access key of this contract limited to claiming the drop. The treasury pays
+        account_deposit for the new account, the GAS allowance of the key and the storage of a token with the
+        traits of the trait table
+    */
+    #[payable]
+    pub fn add_drop_keys(&mut self, public_keys: Vec<PublicKey>, account_deposit: Option<U128>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.treasury_id,
+            "Predecessor must be treasury."
+        );
+        assert!(!public_keys.is_empty(), "Nothing to drop");
+        assert!(
+            public_keys.len() <= MAX_DROP_KEYS,
+            "Cannot add more than {} drop keys at once",
+            MAX_DROP_KEYS
+        );
+        let account_deposit = account_deposit.map(|deposit| deposit.0).unwrap_or(0);
+        let mint_deposit = self.internal_mint_storage_deposit();
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        for public_key in public_keys.iter() {
+            let drop = NftDrop {
+                account_deposit: U128(account_deposit),
+                allowance: U128(DROP_KEY_ALLOWANCE),
+                mint_deposit: U128(mint_deposit),
+            };
+            assert!(self.drops.insert(public_key, &drop).is_none(), "Drop key is already added");
+            Promise::new(env::current_account_id()).add_access_key(
+                public_key.clone(),
+                DROP_KEY_ALLOWANCE,
+                env::current_account_id(),
+                DROP_KEY_METHODS.to_string(),
+            );
+        }
+
+        let required_cost = env::storage_byte_cost() * Balance::from(env::storage_usage() - initial_storage_usage)
+            + (account_deposit + DROP_KEY_ALLOWANCE + mint_deposit) * public_keys.len() as Balance;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            required_cost <= attached_deposit,
+            "Must attach {} yoctoNEAR to fund the drops",
+            required_cost,
+        );
+
+        //if the refund is greater than 1 yocto NEAR, we refund the predecessor that amount
+        let refund = attached_deposit - required_cost;
+        if refund > 1 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+
+    //remove unclaimed drops and their access keys. The treasury gets back the deposits
+    #[payable]
+    pub fn remove_drop_keys(&mut self, public_keys: Vec<PublicKey>) {
+        assert_one_yocto();
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.treasury_id,
+            "Predecessor must be treasury."
+        );
+        assert!(
+            public_keys.len() <= MAX_DROP_KEYS,
+            "Cannot remove more than {} drop keys at once",
+            MAX_DROP_KEYS
+        );
+
+        let initial_storage_usage = env::storage_usage();
+
+        let mut refund: Balance = 0;
+        for public_key in public_keys {
+            if let Some(drop) = self.drops.remove(&public_key) {
+                refund += drop.account_deposit.0 + drop.allowance.0 + drop.mint_deposit.0;
+                Promise::new(env::current_account_id()).delete_key(public_key);
+            }
+        }
+
+        refund += env::storage_byte_cost() * Balance::from(initial_storage_usage - env::storage_usage());
+        if refund > 0 {
+            Promise::new(self.treasury_id.clone()).transfer(refund);
+        }
+    }
+
+    //mint the token of the drop to an existing account. Called with the ephemeral access key of the drop
+    #[private]
+    pub fn claim_drop(&mut self, account_id: AccountId) -> TokenId {
+        let public_key = env::signer_account_pk();
+        let drop = self.internal_take_drop(&public_key);
+        self.internal_finish_drop(public_key, drop.allowance.0);
+
+        //the NEAR for a new account goes to the existing account
+        if drop.account_deposit.0 > 0 {
+            Promise::new(account_id.clone()).transfer(drop.account_deposit.0);
+        }
+
+        //the drop key is an access key of this contract, the treasury funding the drop is the minter
+        self.internal_mint_reserved(account_id, self.treasury_id.clone(), drop.mint_deposit.0, self.treasury_id.clone())
+    }
+
+    /*
+        create a new account with the passed in full access key and mint the token of the drop to it.
+        Called with the ephemeral access key of the drop. The drop is put back if the account isn't created
+    */
+    #[private]
+    pub fn create_account_and_claim_drop(&mut self, new_account_id: AccountId, new_public_key: PublicKey) -> Promise {
+        let public_key = env::signer_account_pk();
+        let drop = self.internal_take_drop(&public_key);
+        assert!(drop.account_deposit.0 > 0, "Drop has no deposit for a new account");
+        assert!(self.nft_total_supply() < self.max_supply, "All tokens minted");
+
+        //the account is created by the linkdrop contract of the network (near or testnet)
+        let linkdrop_id: AccountId = env::current_account_id()
+            .as_str()
+            .rsplit('.')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        ext_linkdrop::ext(linkdrop_id)
+            .with_attached_deposit(drop.account_deposit.0)
+            .with_static_gas(GAS_FOR_CREATE_ACCOUNT)
+            .create_account(new_account_id.clone(), new_public_key)
+        .then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_DROP)
+                .nft_resolve_drop(new_account_id, public_key, drop)
+        )
+    }
+
+    //get the NEAR deposited for a new account by the drop, nothing if there is no such drop
+    pub fn get_drop(&self, public_key: PublicKey) -> Option<U128> {
+        self.drops.get(&public_key).map(|drop| drop.account_deposit)
+    }
+}
+
+#[near_bindgen]
+impl DropResolver for Contract {
+    //resolves the account creation in create_account_and_claim_drop
+    //returns true if the account was created and the token minted to it
+    #[private]
+    fn nft_resolve_drop(&mut self, new_account_id: AccountId, public_key: PublicKey, drop: NftDrop) -> bool {
+        let created = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(false),
+            _ => false,
+        };
+
+        if created {
+            self.internal_finish_drop(public_key, drop.allowance.0);
+            /*
+                the reserve covers the storage of any token with the trait table at the time the drop was added. If the
+                last token was minted meanwhile or the trait table grew, the reserve goes back instead of panicking
+            */
+            if self.nft_total_supply() < self.max_supply && drop.mint_deposit.0 >= self.internal_mint_storage_deposit() {
+                self.internal_mint_reserved(
+                    new_account_id,
+                    self.treasury_id.clone(),
+                    drop.mint_deposit.0,
+                    self.treasury_id.clone(),
+                );
+            } else {
+                Promise::new(self.treasury_id.clone()).transfer(drop.mint_deposit.0);
+            }
+        } else {
+            //a failed account creation is refunded by the linkdrop contract to this contract, the key can claim again
+            self.drops.insert(&public_key, &drop);
+        }
+        created
+    }
+}
+
+impl Contract {
+    //remove the drop of the access key while it is claimed and charge the GAS of the claim to its allowance
+    fn internal_take_drop(&mut self, public_key: &PublicKey) -> NftDrop {
+        let mut drop = self.drops.remove(public_key).expect("No drop");
+        let gas_cost = Balance::from(env::prepaid_gas().0) * DROP_GAS_PRICE;
+        drop.allowance = U128(drop.allowance.0.saturating_sub(gas_cost));
+        drop
+    }
+
+    //delete the access key of the claimed drop and return the unspent allowance to the treasury
+    fn internal_finish_drop(&mut self, public_key: PublicKey, allowance: Balance) {
+        Promise::new(env::current_account_id()).delete_key(public_key);
+        if allowance > 0 {
+            Promise::new(self.treasury_id.clone()).transfer(allowance);
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::*;
 
+//max number of token IDs queried by one batch view (GAS limits this)
+const MAX_BATCH_VIEW: usize = 100;
+
 #[near_bindgen]
 impl Contract {
     //Query for the total supply of NFTs on the contract
@@ -10,18 +13,14 @@ impl Contract {
 
     //Query for nft tokens on the contract regardless of the owner using pagination
     pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken> {
-        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
-        let start = u128::from(from_index.unwrap_or(U128(0)));
+        //the keys of the map are stored in a vector so we can read the page by index without walking the earlier keys
+        let keys = self.tokens_by_id.keys_as_vector();
 
-        //iterate through each token using an iterator
-        self.tokens_by_id.keys()
-            //skip to the index we specified in the start variable
-            .skip(start as usize) 
-            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
-            .take(limit.unwrap_or(50) as usize) 
-            //we'll map the token IDs which are strings into Json Tokens
-            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
-            //since we turned the keys into an iterator, we need to turn it back into a vector to return
+        //iterate through the indexes of the requested page
+        page_range(from_index, limit, keys.len())
+            //we'll get the token ID at every index and map it into a Json Token
+            .map(|index| self.nft_token(keys.get(index).unwrap()).unwrap())
+            //since we turned the indexes into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 
@@ -59,18 +58,41 @@ impl Contract {
             return vec![];
         };
 
-        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
-        let start = u128::from(from_index.unwrap_or(U128(0)));
+        //the elements of the set are stored in a vector so we can read the page by index without walking the earlier elements
+        let keys = tokens.as_vector();
+
+        //iterate through the indexes of the requested page
+        page_range(from_index, limit, keys.len())
+            //we'll get the token ID at every index and map it into a Json Token
+            .map(|index| self.nft_token(keys.get(index).unwrap()).unwrap())
+            //since we turned the indexes into an iterator, we need to turn it back into a vector to return
+            .collect()
+    }
+
+    //Query for many tokens at once. Returns nothing in place of a token ID that doesn't exist
+    pub fn nft_tokens_by_ids(&self, token_ids: Vec<TokenId>) -> Vec<Option<JsonToken>> {
+        assert!(
+            token_ids.len() <= MAX_BATCH_VIEW,
+            "Cannot query more than {} tokens at once",
+            MAX_BATCH_VIEW
+        );
+
+        token_ids.into_iter()
+            .map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+
+    //Query for the owners of many tokens at once. Returns nothing in place of a token ID that doesn't exist
+    pub fn nft_owners_of(&self, token_ids: Vec<TokenId>) -> Vec<Option<AccountId>> {
+        assert!(
+            token_ids.len() <= MAX_BATCH_VIEW,
+            "Cannot query more than {} tokens at once",
+            MAX_BATCH_VIEW
+        );
 
-        //iterate through the keys vector
-        tokens.iter()
-            //skip to the index we specified in the start variable
-            .skip(start as usize) 
-            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
-            .take(limit.unwrap_or(50) as usize) 
-            //we'll map the token IDs which are strings into Json Tokens
-            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
-            //since we turned the keys into an iterator, we need to turn it back into a vector to return
+        token_ids.iter()
+            //read only the token struct, without generating the metadata
+            .map(|token_id| self.tokens_by_id.get(token_id).map(|token| token.owner_id))
             .collect()
     }
 }
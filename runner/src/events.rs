@@ -1,9 +1,11 @@
 use std::fmt;
 
+use near_sdk::json_types::{U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 
 /// Enum that represents the data type of the EventLog.
-/// The enum can either be an NftMint or an NftTransfer.
+/// The enum can either be an NftMint or an NftTransfer of the NFT standard
+/// or one of the runner specific events.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
@@ -12,6 +14,11 @@ use near_sdk::serde::{Deserialize, Serialize};
 pub enum EventLogVariant {
     NftMint(Vec<NftMintLog>),
     NftTransfer(Vec<NftTransferLog>),
+    NftUpdateUser(Vec<NftUpdateUserLog>),
+    NftMetadataFreeze(Vec<NftMetadataFreezeLog>),
+    NftApproveAll(Vec<NftOperatorLog>),
+    NftRevokeOperators(Vec<NftOperatorLog>),
+    NftReferral(Vec<NftReferralLog>),
 }
 
 /// Interface to capture data about an event
@@ -78,6 +85,62 @@ pub struct NftTransferLog {
     pub memo: Option<String>,
 }
 
+/// An event log to capture a change of the token user (rental)
+///
+/// Arguments
+/// * `token_id`: "1"
+/// * `user_id`: "player.near", missing when the user is cleared
+/// * `expires`: nanoseconds timestamp when the user loses access
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftUpdateUserLog {
+    pub token_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+
+    pub expires: U64,
+}
+
+/// An event log to capture a permanent metadata freeze
+///
+/// Arguments
+/// * `token_ids`: ["1", "2"], missing when the metadata of the whole contract is frozen
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMetadataFreezeLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_ids: Option<Vec<String>>,
+}
+
+/// An event log to capture owner level operators being approved or revoked
+///
+/// Arguments
+/// * `owner_id`: "owner.near"
+/// * `operator_ids`: ["market.near"]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftOperatorLog {
+    pub owner_id: String,
+    pub operator_ids: Vec<String>,
+}
+
+/// An event log to capture a referral reward paid for a public mint
+///
+/// Arguments
+/// * `referrer_id`: "influencer.near"
+/// * `buyer_id`: "player.near"
+/// * `token_ids`: ["1"]
+/// * `amount`: yoctoNEAR paid to the referrer
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftReferralLog {
+    pub referrer_id: String,
+    pub buyer_id: String,
+    pub token_ids: Vec<String>,
+    pub amount: U128,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,4 +198,19 @@ mod tests {
         };
         assert_eq!(expected, log.to_string());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn runner_format_update_user() {
+        let expected = r#"EVENT_JSON:{"standard":"runner","version":"1.0.0","event":"nft_update_user","data":[{"token_id":"0","user_id":"player.near","expires":"1680000000000000000"}]}"#;
+        let log = EventLog {
+            standard: "runner".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftUpdateUser(vec![NftUpdateUserLog {
+                token_id: "0".to_string(),
+                user_id: Some("player.near".to_string()),
+                expires: U64(1680000000000000000),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::*;
+
+//max number of entries kept for a token so the storage stays bounded.
+//the mint entry is always kept, the oldest transfers are dropped first
+pub const MAX_HISTORY_LEN: usize = 10;
+
+//max bytes of a transfer entry: the kind, three account IDs of up to 64 characters, no price and the timestamp
+const MAX_TRANSFER_ENTRY_LEN: StorageUsage = 1 + (1 + 4 + 64) + (4 + 64) + (1 + 4 + 64) + 1 + 8;
+
+//bytes reserved at mint for the transfers kept in the history. Transfers attach 1 yoctoNEAR so they can't pay for it
+pub const HISTORY_STORAGE_RESERVE: StorageUsage = (MAX_HISTORY_LEN as StorageUsage - 1) * MAX_TRANSFER_ENTRY_LEN;
+
+//what happened to the token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ProvenanceKind {
+    Mint,
+    Transfer,
+    //the receiver of nft_transfer_call returned the token
+    Revert,
+}
+
+//one entry of the token ownership history
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProvenanceEntry {
+    pub kind: ProvenanceKind,
+    //previous owner, nothing for a mint
+    pub from_id: Option<AccountId>,
+    //new owner
+    pub to_id: AccountId,
+    //approved account which transferred the token on behalf of the owner, the minter for a mint
+    pub authorized_id: Option<AccountId>,
+    //price paid for the mint
+    pub price: Option<U128>,
+    //when it happened, nanoseconds
+    pub timestamp: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    //get the ownership history of the token using pagination, oldest entries first
+    pub fn nft_history(
+        &self,
+        token_id: TokenId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<ProvenanceEntry> {
+        let history = self.token_history.get(&token_id).unwrap_or_default();
+
+        page_range(from_index, limit, history.len() as u64)
+            .map(|index| history[index as usize].clone())
+            .collect()
+    }
+}
+
+impl Contract {
+    //add an entry to the history of the token, compacting it when it grows over the limit
+    pub(crate) fn internal_record_history(&mut self, token_id: &TokenId, entry: ProvenanceEntry) {
+        let mut history = self.token_history.get(token_id).unwrap_or_default();
+        history.push(entry);
+
+        //keep the mint entry and drop the oldest transfer after it
+        if history.len() > MAX_HISTORY_LEN {
+            history.remove(1);
+        }
+
+        self.token_history.insert(token_id, &history);
+    }
+}
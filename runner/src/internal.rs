@@ -1,6 +1,7 @@
 use crate::*;
 use near_sdk::{CryptoHash};
 use std::mem::size_of;
+use std::ops::Range;
 
 //convert the royalty percentage and amount to pay into a payout (U128)
 pub(crate) fn royalty_to_payout(royalty_percentage: u32, amount_to_pay: Balance) -> U128 {
@@ -36,6 +37,20 @@ pub(crate) fn refund_approved_account_ids(
     refund_approved_account_ids_iter(account_id, approved_account_ids.keys())
 }
 
+//refund the storage released by removing some state and send the funds to the passed in account ID
+pub(crate) fn refund_released_storage(account_id: AccountId, storage_released: u64) -> Promise {
+    Promise::new(account_id).transfer(Balance::from(storage_released) * env::storage_byte_cost())
+}
+
+//get the indexes of the requested page in a collection with the passed in length. If we didn't specify a limit, use 50
+pub(crate) fn page_range(from_index: Option<U128>, limit: Option<u64>, len: u64) -> Range<u64> {
+    //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+    let start = std::cmp::min(u128::from(from_index.unwrap_or(U128(0))), len as u128) as u64;
+    //the page ends after "limit" elements or at the end of the collection
+    let end = std::cmp::min(start.saturating_add(limit.unwrap_or(50)), len);
+    start..end
+}
+
 //used to generate a unique prefix in our storage collections (this is to avoid data collisions)
 pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     //get the default hash
@@ -137,7 +152,7 @@ impl Contract {
         }
     }
 
-    //transfers the NFT to the receiver_id (internal method and can't be called directly via CLI).
+    //transfers the NFT to the receiver_id and logs the transfer (internal method and can't be called directly via CLI).
     pub(crate) fn internal_transfer(
         &mut self,
         sender_id: &AccountId,
@@ -146,12 +161,67 @@ impl Contract {
         //we introduce an approval ID so that people with that approval ID can transfer the token
         approval_id: Option<u64>,
         memo: Option<String>,
+    ) -> Token {
+        //change the owner of the token and get back the previous token object
+        let token = self.internal_transfer_token(sender_id, receiver_id, token_id, approval_id);
+
+        //if there was some memo attached, we log it. 
+        if let Some(memo) = memo.as_ref() {
+            env::log_str(&format!("Memo: {}", memo).to_string());
+        }
+
+        // Default the authorized ID to be None for the logs.
+        let mut authorized_id = None;
+        //if the sender isn't the owner of the token (an approved account or an operator), set the authorized ID equal to the sender
+        if sender_id != &token.owner_id {
+            authorized_id = Some(sender_id.to_string());
+        }
+
+        // Construct the transfer log as per the events standard.
+        let nft_transfer_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftTransfer(vec![NftTransferLog {
+                // The optional authorized account ID to transfer the token on behalf of the old owner.
+                authorized_id,
+                // The old owner's account ID.
+                old_owner_id: token.owner_id.to_string(),
+                // The account ID of the new owner of the token.
+                new_owner_id: receiver_id.to_string(),
+                // A vector containing the token IDs as strings.
+                token_ids: vec![token_id.to_string()],
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_transfer_log.to_string());
+        
+        //return the previous token object that was transferred.
+        token
+    }
+
+    //changes the owner of the NFT without logging (internal method and can't be called directly via CLI).
+    pub(crate) fn internal_transfer_token(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        //we introduce an approval ID so that people with that approval ID can transfer the token
+        approval_id: Option<u64>,
     ) -> Token {
         //get the token object by passing in the token_id
         let token = self.tokens_by_id.get(token_id).expect("No token");
 
-        //if the sender doesn't equal the owner, we check if the sender is in the approval list
-		if sender_id != &token.owner_id {
+        //staked tokens are locked in place
+        self.assert_not_staked(token_id);
+
+        //if the sender doesn't equal the owner or one of the owner's operators, we check if the sender is in the approval list
+		if sender_id != &token.owner_id && !self.internal_is_operator(&token.owner_id, sender_id) {
 			//if the token's approved account IDs doesn't contain the sender, we panic
 			if !token.approved_account_ids.contains_key(sender_id) {
 				env::panic_str("Unauthorized");
@@ -173,6 +243,12 @@ impl Contract {
 					actual_approval_id, enforced_approval_id,
 				);
 			}
+
+			//make sure that the approval didn't expire
+			assert!(
+				self.internal_is_approval_active(token_id, token.approved_account_ids[sender_id]),
+				"Approval expired"
+			);
 		}
 
         //we make sure that the sender isn't sending the token to themselves
@@ -196,43 +272,25 @@ impl Contract {
         //insert that new token into the tokens_by_id, replacing the old entry 
         self.tokens_by_id.insert(token_id, &new_token);
 
-        //if there was some memo attached, we log it. 
-        if let Some(memo) = memo.as_ref() {
-            env::log_str(&format!("Memo: {}", memo).to_string());
-        }
-
-        // Default the authorized ID to be None for the logs.
-        let mut authorized_id = None;
-        //if the approval ID was provided, set the authorized ID equal to the sender
-        if approval_id.is_some() {
-            authorized_id = Some(sender_id.to_string());
-        }
+        //the rental ends with the transfer, the previous owner gets back the storage
+        self.internal_clear_user(token_id, &token.owner_id);
 
-        // Construct the transfer log as per the events standard.
-        let nft_transfer_log: EventLog = EventLog {
-            // Standard name ("nep171").
-            standard: NFT_STANDARD_NAME.to_string(),
-            // Version of the standard ("nft-1.0.0").
-            version: NFT_METADATA_SPEC.to_string(),
-            // The data related with the event stored in a vector.
-            event: EventLogVariant::NftTransfer(vec![NftTransferLog {
-                // The optional authorized account ID to transfer the token on behalf of the old owner.
-                authorized_id,
-                // The old owner's account ID.
-                old_owner_id: token.owner_id.to_string(),
-                // The account ID of the new owner of the token.
-                new_owner_id: receiver_id.to_string(),
-                // A vector containing the token IDs as strings.
-                token_ids: vec![token_id.to_string()],
-                // An optional memo to include.
-                memo,
-            }]),
+        //if the sender isn't the owner of the token, the sender is the authorized ID of the transfer
+        let authorized_id = if sender_id != &token.owner_id {
+            Some(sender_id.clone())
+        } else {
+            None
         };
+        self.internal_record_history(token_id, ProvenanceEntry {
+            kind: ProvenanceKind::Transfer,
+            from_id: Some(token.owner_id.clone()),
+            to_id: receiver_id.clone(),
+            authorized_id,
+            price: None,
+            timestamp: U64(env::block_timestamp()),
+        });
 
-        // Log the serialized json.
-        env::log_str(&nft_transfer_log.to_string());
-        
         //return the previous token object that was transferred.
         token
     }
-} 
\ No newline at end of file
+}
@@ -1,10 +1,10 @@
 use std::collections::HashMap;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::{Base64VecU8, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, near_bindgen, AccountId, Balance, CryptoHash, PanicOnDefault, Promise, PromiseOrValue,
+    env, near_bindgen, AccountId, Balance, CryptoHash, PanicOnDefault, Promise, PromiseOrValue, PublicKey, StorageUsage,
 };
 
 use crate::internal::*;
@@ -14,6 +14,16 @@ pub use crate::nft_core::*;
 pub use crate::approval::*;
 pub use crate::royalty::*;
 pub use crate::events::*;
+pub use crate::staking::*;
+pub use crate::rental::*;
+pub use crate::history::*;
+pub use crate::snapshot::*;
+pub use crate::voucher::*;
+pub use crate::referral::*;
+pub use crate::pricing::*;
+pub use crate::traits::*;
+pub use crate::drop::NftDrop;
+pub use crate::claim::ClaimCode;
 
 mod internal;
 mod approval; 
@@ -23,11 +33,27 @@ mod mint;
 mod nft_core; 
 mod royalty; 
 mod events;
+mod staking;
+mod rental;
+mod history;
+mod snapshot;
+mod operator;
+mod voucher;
+mod claim;
+mod drop;
+mod referral;
+mod pricing;
+mod traits;
+mod svg;
 
 /// This spec can be treated like a version of the standard.
 pub const NFT_METADATA_SPEC: &str = "1.0.0";
 /// This is the name of the NFT standard we're using
 pub const NFT_STANDARD_NAME: &str = "nep171";
+/// This is the standard name of the runner specific events (rentals etc.)
+pub const RUNNER_STANDARD_NAME: &str = "runner";
+/// Version of the runner specific events.
+pub const RUNNER_EVENTS_VERSION: &str = "1.0.0";
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -58,6 +84,114 @@ pub struct Contract {
     pub mint_end: U64,
 
     pub perpetual_royalties: HashMap<AccountId, u32>,
+
+    //keeps track of the stake for every staked token ID
+    pub staked_tokens: LookupMap<TokenId, StakeInfo>,
+
+    //keeps track of all the staked token IDs for a given account
+    pub stakes_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+
+    //rewards of unstaked tokens which weren't claimed yet
+    pub staking_rewards_pending: LookupMap<AccountId, Balance>,
+
+    //claimed rewards recorded as points for a future fungible token
+    pub reward_points: LookupMap<AccountId, Balance>,
+
+    //reward points accrued per second for every staked token
+    pub staking_reward_rate: U128,
+
+    //NEAR funded to pay out staking rewards
+    pub staking_reward_pool: U128,
+
+    //pay claimed rewards in NEAR from the pool instead of recording points
+    pub staking_rewards_in_near: bool,
+
+    //reward points accrued by one token staked since the deployment, so a new rate isn't applied to past seconds
+    pub staking_reward_index: U128,
+
+    //until when the reward index is accrued, moved by whole seconds so no fraction of a second is lost
+    pub staking_index_updated_at: U64,
+
+    //number of staked tokens
+    pub staked_tokens_count: u64,
+
+    //rewards accrued up to the reward index and not claimed yet, this part of the pool can't be withdrawn
+    pub staking_rewards_owed: U128,
+
+    //keeps track of the user (renter) for a given token ID
+    pub token_users: LookupMap<TokenId, TokenUser>,
+
+    //keeps track of the ownership history for a given token ID
+    pub token_history: LookupMap<TokenId, Vec<ProvenanceEntry>>,
+
+    //keeps track of the holder snapshots by snapshot ID
+    pub snapshots: LookupMap<u64, Snapshot>,
+
+    //ID of the next snapshot
+    pub next_snapshot_id: u64,
+
+    //once frozen max_supply can never change
+    pub supply_frozen: bool,
+
+    //once frozen the contract and token metadata can never change
+    pub metadata_frozen: bool,
+
+    //keeps track of the final metadata for a given individually frozen token ID
+    pub frozen_token_metadata: LookupMap<TokenId, TokenMetadata>,
+
+    //templates used to generate the token metadata
+    pub token_template: TokenMetadataTemplate,
+
+    //keeps track of when the approvals expire (nanoseconds) by approval ID for a given token ID
+    pub approval_expirations: LookupMap<TokenId, HashMap<u64, u64>>,
+
+    //keeps track of the operators which can transfer all the tokens of a given account ID
+    pub operators_per_owner: LookupMap<AccountId, UnorderedSet<AccountId>>,
+
+    //ed25519 key of the backend which signs the mint vouchers, vouchers are rejected if there is no key
+    pub voucher_signer: Option<PublicKey>,
+
+    //keeps track of the nonces of the redeemed mint vouchers
+    pub used_voucher_nonces: LookupSet<u64>,
+
+    //keeps track of the expiration and the reserved deposit for a given sha256 hash of an unclaimed claim code
+    pub claim_codes: LookupMap<CryptoHash, ClaimCode>,
+
+    //number of registered claim codes which weren't claimed or revoked yet
+    pub claim_codes_count: u64,
+
+    //keeps track of the NEAR held for the drop of a given ephemeral access key
+    pub drops: LookupMap<PublicKey, NftDrop>,
+
+    //share of the mint price paid to the referrer of a public mint, basis points (10000 is 100%)
+    pub referral_bps: u32,
+
+    //keeps track of the referral stats for a given referrer account ID
+    pub referral_stats: UnorderedMap<AccountId, ReferralStats>,
+
+    //referrers with the most minted tokens, sorted
+    pub referral_leaders: LazyOption<Vec<Referrer>>,
+
+    //mint prices for holders, sorted by the number of held tokens
+    pub price_tiers: Vec<PriceTier>,
+
+    //NFT contract whose tokens are counted for the price tiers by nft_mint_partner
+    pub partner_contract_id: Option<AccountId>,
+
+    //categories of traits with weighted options rolled for every minted token
+    pub trait_table: LazyOption<Vec<TraitCategory>>,
+
+    //keeps track of the rolled option index for every trait category of a given token ID
+    pub token_traits: LookupMap<TokenId, Vec<u8>>,
+
+    //keeps track of all the token IDs for a given trait category and option index
+    pub tokens_per_trait: LookupMap<(u8, u8), UnorderedSet<TokenId>>,
+
+    //keeps track of the SVG layer for a given trait category and option index
+    pub trait_layers: LookupMap<(u8, u8), String>,
+
+    //viewBox of the on-chain SVG media composed from the trait layers, nothing means the media is served by base_uri
+    pub svg_view_box: Option<String>,
 }
 
 const DATA_IMAGE_SVG_ICON: &str = "data:image/svg+xml,%3Csvg id='Layer_2' data-name='Layer 2' xmlns='http://www.w3.org/2000/svg' viewBox='0 0 566.1 555.59'%3E%3Cdefs%3E%3Cstyle%3E.cls-1%7Bfill:%23f15a29;%7D%3C/style%3E%3C/defs%3E%3Cpath class='cls-1' d='M708.73,315.12c-51.79-57-126-92.91-208.8-92.91s-157,36-208.7,92.91A284.3,284.3,0,0,0,217,506.91c.05,135,28.75,205.94,75.67,243.24a147.36,147.36,0,0,0,27.31,17.25c-3.78-3-7.42-6.31-10.84-9.66a153.64,153.64,0,0,1-46.47-108.63c-1.75-43.71-2.58-73-3.57-107-.45-17.91-1-37-1.75-60.44,0-88.38,92-154.43,174.26-154.43H568.42c99.77,0,174.26,81.5,174.26,154.83-.71,23-1.23,42-1.78,60-1,34-1.79,63.3-3.54,106.65A156.09,156.09,0,0,1,679,768.18a153.42,153.42,0,0,0,30-18.88c46-37.58,74.06-108.51,74.06-242.39a284.62,284.62,0,0,0-74.32-191.79Z' transform='translate(-216.95 -222.21)'/%3E%3Cpath class='cls-1' d='M564.92,352H435c-74,0-152.76,60-152.76,134,2,63,2.46,94.93,5,158.36.38,48.13,26.18,90,64.43,113.4a134.15,134.15,0,0,0,70.73,20.08H577.51a134.43,134.43,0,0,0,70.78-20.08c38.18-23.46,64-65.23,64.38-113.4,2.58-63.45,3.07-95.39,5.11-158.36C717.75,412,638.92,352,564.92,352ZM422.46,587.21a49.52,49.52,0,0,1-30.31,10.42,51.07,51.07,0,1,1,30.31-10.42ZM607.8,597.65a51.05,51.05,0,0,1-50.46-50.44,50.46,50.46,0,1,1,50.46,50.44Z' transform='translate(-216.95 -222.21)'/%3E%3C/svg%3E";
@@ -73,6 +207,29 @@ pub enum StorageKey {
     TokensPerType,
     TokensPerTypeInner { token_type_hash: CryptoHash },
     TokenTypesLocked,
+    StakedTokens,
+    StakesPerOwner,
+    StakesPerOwnerInner { account_id_hash: CryptoHash },
+    StakingRewardsPending,
+    RewardPoints,
+    TokenUsers,
+    TokenHistory,
+    Snapshots,
+    SnapshotHolders { snapshot_id: u64 },
+    FrozenTokenMetadata,
+    ApprovalExpirations,
+    OperatorsPerOwner,
+    OperatorsPerOwnerInner { account_id_hash: CryptoHash },
+    UsedVoucherNonces,
+    ClaimCodes,
+    Drops,
+    ReferralStats,
+    TraitTable,
+    TokenTraits,
+    TokensPerTrait,
+    TokensPerTraitInner { category_index: u8, option_index: u8 },
+    TraitLayers,
+    ReferralLeaders,
 }
 
 #[near_bindgen]
@@ -111,15 +268,18 @@ impl Contract {
             mint_start,
             mint_end,
             perpetual_royalties,
+            None,
         )
     }
 
     /*
         initialization function (can only be called once).
         this initializes the contract with metadata that was passed in and
-        the owner_id. 
+        the owner_id. The token metadata templates let another collection
+        reuse the contract.
     */
     #[init]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         owner_id: AccountId,
         metadata: NFTContractMetadata,
@@ -128,8 +288,12 @@ impl Contract {
         mint_price: U128,
         mint_start: U64,
         mint_end: U64,
-        perpetual_royalties: Option<HashMap<AccountId, u32>>
+        perpetual_royalties: Option<HashMap<AccountId, u32>>,
+        token_template: Option<TokenMetadataTemplate>,
     ) -> Self {
+            //make sure the metadata follows the standard
+            metadata.assert_valid();
+
             // create a royalty map to store in the contract
             let mut royalty = HashMap::new();
 
@@ -161,6 +325,43 @@ impl Contract {
             mint_end,
             max_supply,
             perpetual_royalties: royalty,
+            staked_tokens: LookupMap::new(StorageKey::StakedTokens.try_to_vec().unwrap()),
+            stakes_per_owner: LookupMap::new(StorageKey::StakesPerOwner.try_to_vec().unwrap()),
+            staking_rewards_pending: LookupMap::new(StorageKey::StakingRewardsPending.try_to_vec().unwrap()),
+            reward_points: LookupMap::new(StorageKey::RewardPoints.try_to_vec().unwrap()),
+            staking_reward_rate: U128(0),
+            staking_reward_pool: U128(0),
+            staking_rewards_in_near: false,
+            staking_reward_index: U128(0),
+            staking_index_updated_at: U64(env::block_timestamp()),
+            staked_tokens_count: 0,
+            staking_rewards_owed: U128(0),
+            token_users: LookupMap::new(StorageKey::TokenUsers.try_to_vec().unwrap()),
+            token_history: LookupMap::new(StorageKey::TokenHistory.try_to_vec().unwrap()),
+            snapshots: LookupMap::new(StorageKey::Snapshots.try_to_vec().unwrap()),
+            next_snapshot_id: 0,
+            supply_frozen: false,
+            metadata_frozen: false,
+            frozen_token_metadata: LookupMap::new(StorageKey::FrozenTokenMetadata.try_to_vec().unwrap()),
+            //the Chubby Runners templates are used unless other templates were passed in
+            token_template: token_template.unwrap_or_default(),
+            approval_expirations: LookupMap::new(StorageKey::ApprovalExpirations.try_to_vec().unwrap()),
+            operators_per_owner: LookupMap::new(StorageKey::OperatorsPerOwner.try_to_vec().unwrap()),
+            voucher_signer: None,
+            used_voucher_nonces: LookupSet::new(StorageKey::UsedVoucherNonces.try_to_vec().unwrap()),
+            claim_codes: LookupMap::new(StorageKey::ClaimCodes.try_to_vec().unwrap()),
+            claim_codes_count: 0,
+            drops: LookupMap::new(StorageKey::Drops.try_to_vec().unwrap()),
+            referral_bps: 0,
+            referral_stats: UnorderedMap::new(StorageKey::ReferralStats.try_to_vec().unwrap()),
+            referral_leaders: LazyOption::new(StorageKey::ReferralLeaders.try_to_vec().unwrap(), None),
+            price_tiers: vec![],
+            partner_contract_id: None,
+            trait_table: LazyOption::new(StorageKey::TraitTable.try_to_vec().unwrap(), None),
+            token_traits: LookupMap::new(StorageKey::TokenTraits.try_to_vec().unwrap()),
+            tokens_per_trait: LookupMap::new(StorageKey::TokensPerTrait.try_to_vec().unwrap()),
+            trait_layers: LookupMap::new(StorageKey::TraitLayers.try_to_vec().unwrap()),
+            svg_view_box: None,
         };
 
         //return the Contract object
@@ -181,27 +382,66 @@ impl Contract {
               "Predecessor must be contract owner."
         );
         assert!(
-            base_uri.len() <= 100,
-            "Base URI must be less then 100 chars"
+            max_supply >= self.nft_total_supply(),
+            "Max supply must not be less then {} minted tokens",
+            self.nft_total_supply().0
+        );
+        assert!(
+            !self.supply_frozen || max_supply == self.max_supply,
+            "Supply is frozen"
         );
 
         self.max_supply = max_supply;
 
+        let metadata = NFTContractMetadata {
+            spec: format!("nft-{}", NFT_METADATA_SPEC),
+            name: name.to_string(),
+            symbol: "RUNNER".to_string(),
+            icon,
+            base_uri: Some(base_uri.to_string()),
+            reference: None,
+            reference_hash: None,
+        };
+        metadata.assert_valid();
+
+        //once the metadata is frozen only max_supply can still be changed
+        if self.metadata_frozen {
+            let frozen_metadata = self.metadata.get().unwrap();
+            assert!(
+                frozen_metadata.name == metadata.name
+                    && frozen_metadata.base_uri == metadata.base_uri
+                    && frozen_metadata.icon == metadata.icon,
+                "Metadata is frozen"
+            );
+            return;
+        }
+
         self.metadata = LazyOption::new(
            StorageKey::NFTContractMetadata.try_to_vec().unwrap(),
-           Some(&NFTContractMetadata {
-                   spec: "nft-1.0.0".to_string(),
-                   name: name.to_string(),
-                   symbol: "RUNNER".to_string(),
-                   icon,
-                   base_uri: Some(base_uri.to_string()),
-                   reference: None,
-                   reference_hash: None,
-           }),
-        )
+           Some(&metadata),
+        );
 
         //todo: event log
     }
+
+    //permanently fix max_supply so collectors can trust the cap
+    #[payable]
+    pub fn freeze_supply(&mut self) {
+        assert_one_yocto();
+        assert_eq!(
+              &env::predecessor_account_id(),
+              &self.owner_id,
+              "Predecessor must be contract owner."
+        );
+        assert!(!self.supply_frozen, "Supply is already frozen");
+
+        self.supply_frozen = true;
+    }
+
+    //whether max_supply is frozen
+    pub fn supply_frozen(&self) -> bool {
+        self.supply_frozen
+    }
 }
 
 #[cfg(test)]
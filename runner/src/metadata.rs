@@ -36,6 +36,35 @@ pub struct TokenMetadata {
     pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
 }
 
+//templates of the token metadata, every "{id}" is replaced with the token ID
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadataTemplate {
+    pub title: Option<String>,       // ex. "Chubby Runner #{id}"
+    pub description: Option<String>, // free-form description
+    pub media: Option<String>,       // path relative to base_uri or URL, ex. "img/{id}.png"
+    pub reference: Option<String>,   // path relative to base_uri or URL, ex. "data/{id}.json"
+}
+
+impl Default for TokenMetadataTemplate {
+    //the templates of the Chubby Runners collection
+    fn default() -> Self {
+        Self {
+            title: Some("Chubby Runner #{id}".to_string()),
+            description: Some("Chubby Runners are designed to provide the ultimate play & earn experience. We believe in rewarding players for their effort, skill, and loyalty.".to_string()),
+            media: Some("img/{id}.png".to_string()),
+            reference: Some("data/{id}.json".to_string()),
+        }
+    }
+}
+
+impl TokenMetadataTemplate {
+    //fill the template in with the token ID
+    pub fn render(template: &Option<String>, token_id: &TokenId) -> Option<String> {
+        template.as_ref().map(|template| template.replace("{id}", token_id))
+    }
+}
+
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Token {
     //owner of the token
@@ -62,6 +91,48 @@ pub struct JsonToken {
     pub royalty: HashMap<AccountId, u32>,
 }
 
+impl NFTContractMetadata {
+    //make sure the contract metadata follows the NEP-177 rules
+    pub fn assert_valid(&self) {
+        assert!(self.spec.starts_with("nft-"), "Spec must start with nft-");
+        if let Some(base_uri) = &self.base_uri {
+            assert!(
+                base_uri.len() <= 100,
+                "Base URI must be less then 100 chars"
+            );
+        }
+        assert_eq!(
+            self.reference.is_some(),
+            self.reference_hash.is_some(),
+            "Reference and reference hash must be present together"
+        );
+        assert_hash_len(&self.reference_hash, "Reference hash");
+    }
+}
+
+impl TokenMetadata {
+    /*
+        make sure the token metadata follows the NEP-177 rules. The media and reference generated from the
+        templates point to off-chain files whose hashes aren't known on-chain, so they can go without the hashes
+    */
+    pub fn assert_valid(&self) {
+        assert!(self.media.is_some() || self.media_hash.is_none(), "Media hash requires media");
+        assert_hash_len(&self.media_hash, "Media hash");
+        assert!(
+            self.reference.is_some() || self.reference_hash.is_none(),
+            "Reference hash requires reference"
+        );
+        assert_hash_len(&self.reference_hash, "Reference hash");
+    }
+}
+
+//a sha256 hash is always 32 bytes
+fn assert_hash_len(hash: &Option<Base64VecU8>, name: &str) {
+    if let Some(hash) = hash {
+        assert_eq!(hash.0.len(), 32, "{} has to be 32 bytes", name);
+    }
+}
+
 pub trait NonFungibleTokenMetadata {
     //view call for returning the contract metadata
     fn nft_metadata(&self) -> NFTContractMetadata;
@@ -72,4 +143,147 @@ impl NonFungibleTokenMetadata for Contract {
     fn nft_metadata(&self) -> NFTContractMetadata {
         self.metadata.get().unwrap()
     }
-}
\ No newline at end of file
+}
+
+#[near_bindgen]
+impl Contract {
+    //permanently reject any changes of the contract metadata (name, base_uri, icon) and the token metadata
+    #[payable]
+    pub fn freeze_metadata(&mut self) {
+        assert_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        self.assert_metadata_not_frozen();
+
+        self.metadata_frozen = true;
+
+        log_metadata_freeze(None);
+    }
+
+    /*
+        permanently fix the metadata of a single token. The media and reference are resolved against the
+        current base_uri so they don't depend on the gateway settings anymore, the hashes of the final files
+        can be attached as well.
+    */
+    #[payable]
+    pub fn freeze_token_metadata(
+        &mut self,
+        token_id: TokenId,
+        media_hash: Option<Base64VecU8>,
+        reference_hash: Option<Base64VecU8>,
+    ) {
+        assert_at_least_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        self.assert_metadata_not_frozen();
+        assert!(self.tokens_by_id.get(&token_id).is_some(), "No token");
+        assert!(
+            self.frozen_token_metadata.get(&token_id).is_none(),
+            "Token metadata is already frozen"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+
+        let mut metadata = self.internal_token_metadata(&token_id);
+        if let Some(base_uri) = self.metadata.get().unwrap().base_uri {
+            //a trailing slash of the base_uri would end up doubled
+            let base_uri = base_uri.trim_end_matches('/');
+            //the on-chain SVG media is a data URL which doesn't need the base_uri
+            metadata.media = metadata.media.map(|media| {
+                if media.starts_with("data:") {
+                    media
+                } else {
+                    format!("{}/{}", base_uri, media)
+                }
+            });
+            metadata.reference = metadata.reference.map(|reference| format!("{}/{}", base_uri, reference));
+        }
+        metadata.media_hash = media_hash;
+        metadata.reference_hash = reference_hash;
+        metadata.updated_at = Some(env::block_timestamp_ms());
+        metadata.assert_valid();
+        self.frozen_token_metadata.insert(&token_id, &metadata);
+
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        log_metadata_freeze(Some(vec![token_id]));
+    }
+
+    //change the templates used to generate the token metadata
+    #[payable]
+    pub fn set_token_template(&mut self, template: TokenMetadataTemplate) {
+        assert_at_least_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        self.assert_metadata_not_frozen();
+
+        let initial_storage_usage = env::storage_usage();
+
+        self.token_template = template;
+
+        //pay for the longer templates with the attached deposit
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    //get the templates used to generate the token metadata
+    pub fn get_token_template(&self) -> TokenMetadataTemplate {
+        self.token_template.clone()
+    }
+
+    //whether the contract and token metadata can't be changed anymore
+    pub fn metadata_frozen(&self) -> bool {
+        self.metadata_frozen
+    }
+
+    //whether the metadata of the token can't be changed anymore
+    pub fn token_metadata_frozen(&self, token_id: TokenId) -> bool {
+        self.metadata_frozen || self.frozen_token_metadata.get(&token_id).is_some()
+    }
+}
+
+impl Contract {
+    //make sure the metadata can be changed
+    pub(crate) fn assert_metadata_not_frozen(&self) {
+        assert!(!self.metadata_frozen, "Metadata is frozen");
+    }
+
+    //generate the metadata of the token from the templates
+    pub(crate) fn internal_token_metadata(&self, token_id: &TokenId) -> TokenMetadata {
+        TokenMetadata {
+            title: TokenMetadataTemplate::render(&self.token_template.title, token_id),
+            description: TokenMetadataTemplate::render(&self.token_template.description, token_id),
+            media: self
+                .internal_token_svg_media(token_id)
+                .or_else(|| TokenMetadataTemplate::render(&self.token_template.media, token_id)),
+            reference: TokenMetadataTemplate::render(&self.token_template.reference, token_id),
+            copies: Some(1u64),
+            media_hash: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: self.internal_token_traits_extra(token_id),
+            reference_hash: None,
+        }
+    }
+}
+
+//log the metadata freeze as per the events standard, no token IDs means the whole contract
+fn log_metadata_freeze(token_ids: Option<Vec<TokenId>>) {
+    let nft_metadata_freeze_log: EventLog = EventLog {
+        standard: RUNNER_STANDARD_NAME.to_string(),
+        version: RUNNER_EVENTS_VERSION.to_string(),
+        event: EventLogVariant::NftMetadataFreeze(vec![NftMetadataFreezeLog { token_ids }]),
+    };
+
+    env::log_str(&nft_metadata_freeze_log.to_string());
+}
@@ -1,107 +1,277 @@
 use crate::*;
 
+//max number of tokens minted by one nft_airdrop call (GAS limits this)
+const MAX_AIRDROP: usize = 50;
+//yoctoNEAR which covers the storage of any minted token without traits (1500 bytes)
+const MINT_STORAGE_DEPOSIT: Balance = 15_000_000_000_000_000_000_000;
+//yoctoNEAR which covers the storage of one rolled trait category of a minted token (250 bytes)
+const TRAIT_STORAGE_DEPOSIT: Balance = 2_500_000_000_000_000_000_000;
+
 #[near_bindgen]
 impl Contract {
+    //mint the next token for the receiver at the tiered price of the buyer. The referrer of a public mint gets referral_bps of the price
     #[payable]
     pub fn nft_mint(
         &mut self,
         receiver_id: AccountId,
+        referrer_id: Option<AccountId>,
     ) {
-        let supply = self.nft_total_supply();
-        assert!(supply < self.max_supply, "All tokens minted");
-
-        let next_token_id: TokenId = supply.0.to_string();
-
-        //measure the initial storage being used on the contract
-        let initial_storage_usage = env::storage_usage();
-
-        let mut need_to_attach: U128 = self.mint_price;
-
-        let is_treasury = env::predecessor_account_id() == self.treasury_id;
+        let buyer_id = env::predecessor_account_id();
+        let is_treasury = buyer_id == self.treasury_id;
         let attached_deposit: U128 = U128(env::attached_deposit());
 
-        if is_treasury {
-            need_to_attach = U128(1);
-        } else {
+        let mut need_to_attach: U128 = U128(1);
+        let mut price: U128 = U128(0);
+        if !is_treasury {
             assert!(near_sdk::env::block_timestamp() > self.mint_start.0, "Minting will start {}, now {}", self.mint_start.0, near_sdk::env::block_timestamp());
             assert!(near_sdk::env::block_timestamp() < self.mint_end.0, "Minting is over {}, now {}", self.mint_end.0, near_sdk::env::block_timestamp());
+            price = self.internal_mint_price_for(&buyer_id, 0);
+            need_to_attach = price;
         }
 
         // check attached amount of NEAR
         assert!(
                 need_to_attach <= attached_deposit,
                 "Must attach {} yoctoNEAR to mint new token, you attached {}",
-                self.mint_price.0,
+                need_to_attach.0,
                 attached_deposit.0
             );
 
-        if !is_treasury {
-            Promise::new(self.treasury_id.clone()).transfer(self.mint_price.0);
+        let (_, required_cost) = self.internal_paid_mint(
+            buyer_id.clone(),
+            receiver_id,
+            if is_treasury { None } else { referrer_id },
+            price,
+        );
+
+        //make sure that the attached deposit is greater than or equal to the required cost
+        assert!(
+            required_cost <= attached_deposit.0,
+            "Must attach {} yoctoNEAR to cover storage and mint price",
+            required_cost,
+        );
+
+        //get the refund amount from the attached deposit - required cost
+        let refund = attached_deposit.0 - required_cost;
+
+        //if the refund is greater than 1 yocto NEAR, we refund the buyer that amount
+        if refund > 1 {
+            Promise::new(buyer_id).transfer(refund);
         }
+    }
 
-        //specify the token struct that contains the owner ID 
-        let token = Token {
-            //set the owner ID equal to the receiver ID passed into the function
-            owner_id: receiver_id,
-            //we set the approved account IDs to the default value (an empty map)
-            approved_account_ids: Default::default(),
-            //the next approval ID is set to 0
-            next_approval_id: 0,
-        };
+    //mint many tokens for free, one for every receiver. Only the treasury can airdrop
+    #[payable]
+    pub fn nft_airdrop(
+        &mut self,
+        receivers: Vec<AccountId>,
+    ) {
+        assert_at_least_one_yocto();
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.treasury_id,
+            "Predecessor must be treasury."
+        );
+        assert!(!receivers.is_empty(), "Nothing to airdrop");
+        assert!(
+            receivers.len() <= MAX_AIRDROP,
+            "Cannot airdrop more than {} tokens at once",
+            MAX_AIRDROP
+        );
 
-        //insert the token ID and token struct and make sure that the token doesn't exist
+        let supply = self.nft_total_supply().0;
         assert!(
-            self.tokens_by_id.insert(&next_token_id, &token).is_none(),
-            "Token already exists"
+            supply + receivers.len() as u128 <= self.max_supply.0,
+            "Only {} tokens left to mint",
+            self.max_supply.0.saturating_sub(supply)
         );
 
-        //call the internal method for adding the token to the owner
-        self.internal_add_token_to_owner(&token.owner_id, &next_token_id);
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+        let minted = receivers.len() as StorageUsage;
 
-        //calculate the required storage which was the used - initial
-        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+        //one log entry for every owner with all the token IDs minted for them
+        let mut mint_logs: Vec<NftMintLog> = vec![];
 
-        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        for (index, receiver_id) in receivers.into_iter().enumerate() {
+            let token_id: TokenId = (supply + index as u128).to_string();
+            self.internal_mint(&token_id, receiver_id.clone(), self.treasury_id.clone(), U128(0), None);
 
-        //get how much it would cost to store the information and mint cost
-        let mut required_cost = env::storage_byte_cost() * Balance::from(required_storage_in_bytes);
-        if !is_treasury {
-            required_cost += self.mint_price.0;
+            let owner_id = receiver_id.to_string();
+            if let Some(mint_log) = mint_logs.iter_mut().find(|log| log.owner_id == owner_id) {
+                mint_log.token_ids.push(token_id);
+            } else {
+                mint_logs.push(NftMintLog {
+                    owner_id,
+                    token_ids: vec![token_id],
+                    memo: None,
+                });
+            }
         }
 
-        //make sure that the attached deposit is greater than or equal to the required cost
-        assert!(
-            required_cost <= attached_deposit.0,
-            "Must attach {} yoctoNEAR to cover storage and mint price",
-            required_cost,
-        );
+        //the treasury pays for the storage of all the tokens and their future history at once
+        refund_deposit(env::storage_usage() - initial_storage_usage + HISTORY_STORAGE_RESERVE * minted);
 
-        //get the refund amount from the attached deposit - required cost
-        let refund = attached_deposit.0 - required_cost;
+        // Construct the mint log as per the events standard.
+        let nft_mint_log: EventLog = EventLog {
+            standard: NFT_STANDARD_NAME.to_string(),
+            version: NFT_METADATA_SPEC.to_string(),
+            event: EventLogVariant::NftMint(mint_logs),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_mint_log.to_string());
+    }
+}
+
+impl Contract {
+    /*
+        mint the next token for the receiver paid by the buyer and get its ID and the cost of the mint, the price and the storage.
+        The price goes to the treasury minus the referral reward, the caller takes care of the deposit
+    */
+    pub(crate) fn internal_paid_mint(
+        &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
+        referrer_id: Option<AccountId>,
+        price: U128,
+    ) -> (TokenId, Balance) {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //create the token for the receiver
+        let next_token_id = self.internal_mint_next(receiver_id.clone(), buyer_id.clone(), price, None);
+
+        if price.0 > 0 {
+            //the referrer gets a share of the price, the rest goes to the treasury
+            let referral_reward = if let Some(referrer_id) = referrer_id {
+                self.internal_reward_referrer(&buyer_id, &referrer_id, &receiver_id, &next_token_id, price)
+            } else {
+                0
+            };
+            Promise::new(self.treasury_id.clone()).transfer(price.0 - referral_reward);
+        }
+
+        //calculate the required storage which was the used - initial and the reserve for the history
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage + HISTORY_STORAGE_RESERVE;
 
-        //if the refund is greater than 1 yocto NEAR, we refund the predecessor that amount
+        //get how much it would cost to store the information and mint cost
+        let required_cost = env::storage_byte_cost() * Balance::from(required_storage_in_bytes) + price.0;
+        (next_token_id, required_cost)
+    }
+
+    /*
+        mint the next token for the receiver with the storage paid from a deposit reserved in advance. The storage
+        must fit within the reserve, the unused part of it is refunded to the passed in account ID
+    */
+    pub(crate) fn internal_mint_reserved(
+        &mut self,
+        receiver_id: AccountId,
+        minter_id: AccountId,
+        mint_deposit: Balance,
+        refund_to: AccountId,
+    ) -> TokenId {
+        let initial_storage_usage = env::storage_usage();
+
+        let token_id = self.internal_mint_next(receiver_id, minter_id, U128(0), None);
+
+        let storage_cost =
+            env::storage_byte_cost() * Balance::from(env::storage_usage() - initial_storage_usage + HISTORY_STORAGE_RESERVE);
+        assert!(
+            storage_cost <= mint_deposit,
+            "Token storage costs {} yoctoNEAR, only {} is reserved",
+            storage_cost,
+            mint_deposit
+        );
+        let refund = mint_deposit - storage_cost;
         if refund > 1 {
-            Promise::new(env::predecessor_account_id()).transfer(refund);
+            Promise::new(refund_to).transfer(refund);
         }
 
+        token_id
+    }
+
+    //get the deposit which covers the storage of any minted token with the traits of the trait table
+    pub(crate) fn internal_mint_storage_deposit(&self) -> Balance {
+        let categories = self.trait_table.get().map(|categories| categories.len()).unwrap_or(0);
+        MINT_STORAGE_DEPOSIT
+            + TRAIT_STORAGE_DEPOSIT * categories as Balance
+            + env::storage_byte_cost() * Balance::from(HISTORY_STORAGE_RESERVE)
+    }
+
+    //mint the next token for the receiver and log the mint, the caller takes care of the storage and the price
+    pub(crate) fn internal_mint_next(
+        &mut self,
+        receiver_id: AccountId,
+        minter_id: AccountId,
+        price: U128,
+        pinned_trait: Option<(u8, u8)>,
+    ) -> TokenId {
+        let supply = self.nft_total_supply();
+        assert!(supply < self.max_supply, "All tokens minted");
+        let token_id: TokenId = supply.0.to_string();
+
+        self.internal_mint(&token_id, receiver_id.clone(), minter_id, price, pinned_trait);
+
         // Construct the mint log as per the events standard.
         let nft_mint_log: EventLog = EventLog {
-            // Standard name ("nep171").
             standard: NFT_STANDARD_NAME.to_string(),
-            // Version of the standard ("nft-1.0.0").
             version: NFT_METADATA_SPEC.to_string(),
-            // The data related with the event stored in a vector.
             event: EventLogVariant::NftMint(vec![NftMintLog {
-                // Owner of the token.
-                owner_id: token.owner_id.to_string(),
-                // Vector of token IDs that were minted.
-                token_ids: vec![next_token_id.to_string()],
-                // An optional memo to include.
+                owner_id: receiver_id.to_string(),
+                token_ids: vec![token_id.clone()],
                 memo: None,
             }]),
         };
 
         // Log the serialized json.
         env::log_str(&nft_mint_log.to_string());
+
+        token_id
+    }
+
+    //create the token for the receiver (internal method and can't be called directly via CLI).
+    pub(crate) fn internal_mint(
+        &mut self,
+        token_id: &TokenId,
+        receiver_id: AccountId,
+        //account which minted the token, recorded in the history
+        minter_id: AccountId,
+        //price paid for the mint, recorded in the history
+        price: U128,
+        //category and option indexes the token gets instead of rolling that category
+        pinned_trait: Option<(u8, u8)>,
+    ) {
+        //specify the token struct that contains the owner ID 
+        let token = Token {
+            //set the owner ID equal to the receiver ID passed into the function
+            owner_id: receiver_id,
+            //we set the approved account IDs to the default value (an empty map)
+            approved_account_ids: Default::default(),
+            //the next approval ID is set to 0
+            next_approval_id: 0,
+        };
+
+        //insert the token ID and token struct and make sure that the token doesn't exist
+        assert!(
+            self.tokens_by_id.insert(token_id, &token).is_none(),
+            "Token already exists"
+        );
+
+        //call the internal method for adding the token to the owner
+        self.internal_add_token_to_owner(&token.owner_id, token_id);
+
+        //the mint starts the ownership history of the token
+        self.internal_record_history(token_id, ProvenanceEntry {
+            kind: ProvenanceKind::Mint,
+            from_id: None,
+            to_id: token.owner_id,
+            authorized_id: Some(minter_id),
+            price: Some(price),
+            timestamp: U64(env::block_timestamp()),
+        });
+
+        //roll the traits of the token
+        self.internal_roll_traits(token_id, pinned_trait);
     }
-}
\ No newline at end of file
+}
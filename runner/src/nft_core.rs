@@ -3,6 +3,8 @@ use near_sdk::{ext_contract, Gas, PromiseResult};
 
 const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(10_000_000_000_000);
 const GAS_FOR_NFT_ON_TRANSFER: Gas = Gas(25_000_000_000_000);
+//max number of tokens moved by one nft_batch_transfer call (GAS limits this)
+const MAX_BATCH_TRANSFER: usize = 50;
 
 pub trait NonFungibleTokenCore {
     //transfers an NFT to a receiver ID
@@ -50,7 +52,7 @@ trait NonFungibleTokenReceiver {
     this is stored on THIS contract and is meant to analyze what happened in the cross contract call when nft_on_transfer was called
     as part of the nft_transfer_call method
 */ 
-trait NonFungibleTokenResolver {
+pub trait NonFungibleTokenResolver {
     fn nft_resolve_transfer(
         &mut self,
         //we introduce an authorized ID for logging the transfer event
@@ -58,8 +60,8 @@ trait NonFungibleTokenResolver {
         owner_id: AccountId,
         receiver_id: AccountId,
         token_id: TokenId,
-        //we introduce the approval map so we can keep track of what the approvals were before the transfer
-        approved_account_ids: HashMap<AccountId, u64>,
+        //we introduce the approvals so we can keep track of what the approvals and their expirations were before the transfer
+        previous_approvals: PreviousApprovals,
         //we introduce a memo for logging the transfer event
         memo: Option<String>,
     ) -> bool;
@@ -97,6 +99,8 @@ impl NonFungibleTokenCore for Contract {
             previous_token.owner_id.clone(),
             &previous_token.approved_account_ids,
         );
+        //and by their expirations
+        self.internal_prune_approval_expirations(&token_id, &previous_token.owner_id);
     }
 
     //implementation of the transfer call method. This will transfer the NFT and call a method on the receiver_id contract
@@ -125,6 +129,9 @@ impl NonFungibleTokenCore for Contract {
             memo.clone(),
         );
 
+        //the expirations of the previous approvals are kept aside until the transfer is resolved so a revert restores them
+        let approval_expirations = self.approval_expirations.remove(&token_id).unwrap_or_default();
+
         //default the authorized_id to none
         let mut authorized_id = None; 
         //if the sender isn't the owner of the token, we set the authorized ID equal to the sender.
@@ -152,7 +159,10 @@ impl NonFungibleTokenCore for Contract {
                     previous_token.owner_id,
                     receiver_id,
                     token_id,
-                    previous_token.approved_account_ids,
+                    PreviousApprovals {
+                        approved_account_ids: previous_token.approved_account_ids,
+                        expirations: approval_expirations,
+                    },
                     memo, // we introduce a memo for logging in the events standard
                 )
         ).into()
@@ -162,21 +172,11 @@ impl NonFungibleTokenCore for Contract {
     fn nft_token(&self, token_id: TokenId) -> Option<JsonToken> {
         //if there is some token ID in the tokens_by_id collection
         if let Some(token) = self.tokens_by_id.get(&token_id) {
-            //we'll generate the metadata for that token
-            let metadata = TokenMetadata {
-                title: Some(format!("Chubby Runner #{}", token_id).to_string()),
-                description: Some("Chubby Runners are designed to provide the ultimate play & earn experience. We believe in rewarding players for their effort, skill, and loyalty.".to_string()),
-                media: Some(format!("img/{}.png", token_id).to_string()),
-                reference: Some(format!("data/{}.json", token_id).to_string()),
-                copies: Some(1u64),
-                media_hash: None,
-                issued_at: None,
-                expires_at: None,
-                starts_at: None,
-                updated_at: None,
-                extra: None,
-                reference_hash: None,
-            };
+            //we'll get the frozen metadata of the token or generate it
+            let metadata = self
+                .frozen_token_metadata
+                .get(&token_id)
+                .unwrap_or_else(|| self.internal_token_metadata(&token_id));
 
             //we return the JsonToken (wrapped by Some since we return an option)
             Some(JsonToken {
@@ -192,6 +192,77 @@ impl NonFungibleTokenCore for Contract {
     }
 }
 
+#[near_bindgen]
+impl Contract {
+    //transfers many NFTs in one call. The sender must be the owner or an approved account of every token
+    #[payable]
+    pub fn nft_batch_transfer(&mut self, token_ids: Vec<(TokenId, AccountId)>) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+        assert!(!token_ids.is_empty(), "Nothing to transfer");
+        assert!(
+            token_ids.len() <= MAX_BATCH_TRANSFER,
+            "Cannot transfer more than {} tokens at once",
+            MAX_BATCH_TRANSFER
+        );
+
+        let sender_id = env::predecessor_account_id();
+
+        //storage released by the approved account IDs for every previous owner
+        let mut storage_released: HashMap<AccountId, u64> = HashMap::new();
+        //one log entry for every old owner, new owner and authorized ID
+        let mut transfer_logs: Vec<NftTransferLog> = vec![];
+
+        for (token_id, receiver_id) in token_ids {
+            let previous_token = self.internal_transfer_token(&sender_id, &receiver_id, &token_id, None);
+
+            let released: u64 = previous_token.approved_account_ids.keys().map(bytes_for_approved_account_id).sum();
+            *storage_released.entry(previous_token.owner_id.clone()).or_insert(0) += released;
+            self.internal_prune_approval_expirations(&token_id, &previous_token.owner_id);
+
+            //if the sender isn't the owner of the token, we set the authorized ID equal to the sender
+            let authorized_id = if sender_id != previous_token.owner_id {
+                Some(sender_id.to_string())
+            } else {
+                None
+            };
+            let old_owner_id = previous_token.owner_id.to_string();
+            let new_owner_id = receiver_id.to_string();
+
+            if let Some(transfer_log) = transfer_logs.iter_mut().find(|log| {
+                log.authorized_id == authorized_id && log.old_owner_id == old_owner_id && log.new_owner_id == new_owner_id
+            }) {
+                transfer_log.token_ids.push(token_id);
+            } else {
+                transfer_logs.push(NftTransferLog {
+                    authorized_id,
+                    old_owner_id,
+                    new_owner_id,
+                    token_ids: vec![token_id],
+                    memo: None,
+                });
+            }
+        }
+
+        //we refund the previous owners for releasing the storage used up by the approved account IDs
+        for (owner_id, released) in storage_released {
+            if released > 0 {
+                refund_released_storage(owner_id, released);
+            }
+        }
+
+        // Construct the transfer log as per the events standard.
+        let nft_transfer_log: EventLog = EventLog {
+            standard: NFT_STANDARD_NAME.to_string(),
+            version: NFT_METADATA_SPEC.to_string(),
+            event: EventLogVariant::NftTransfer(transfer_logs),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_transfer_log.to_string());
+    }
+}
+
 #[near_bindgen]
 impl NonFungibleTokenResolver for Contract {
     //resolves the cross contract call when calling nft_on_transfer in the nft_transfer_call method
@@ -204,11 +275,13 @@ impl NonFungibleTokenResolver for Contract {
         owner_id: AccountId,
         receiver_id: AccountId,
         token_id: TokenId,
-        //we introduce the approval map so we can keep track of what the approvals were before the transfer
-        approved_account_ids: HashMap<AccountId, u64>,
+        //we introduce the approvals so we can keep track of what the approvals and their expirations were before the transfer
+        previous_approvals: PreviousApprovals,
         //we introduce a memo for logging the transfer event
         memo: Option<String>,
     ) -> bool {
+        let PreviousApprovals { approved_account_ids, expirations: approval_expirations } = previous_approvals;
+
         // Whether receiver wants to return token back to the sender, based on `nft_on_transfer`
         // call result.
         if let PromiseResult::Successful(value) = env::promise_result(0) {
@@ -221,7 +294,8 @@ impl NonFungibleTokenResolver for Contract {
                         revert the original transfer and thus we can just return true since nothing went wrong.
                     */
                     //we refund the owner for releasing the storage used up by the approved account IDs
-                    refund_approved_account_ids(owner_id, &approved_account_ids);
+                    refund_approved_account_ids(owner_id.clone(), &approved_account_ids);
+                    self.internal_refund_approval_expirations(&token_id, approval_expirations, &owner_id);
                     return true;
                 }
             }
@@ -229,9 +303,11 @@ impl NonFungibleTokenResolver for Contract {
 
         //get the token object if there is some token object
         let mut token = if let Some(token) = self.tokens_by_id.get(&token_id) {
-            if token.owner_id != receiver_id {
+            //a token staked by the receiver stays locked with the receiver, so it can't be returned either
+            if token.owner_id != receiver_id || self.staked_tokens.contains_key(&token_id) {
                 //we refund the owner for releasing the storage used up by the approved account IDs
-                refund_approved_account_ids(owner_id, &approved_account_ids);
+                refund_approved_account_ids(owner_id.clone(), &approved_account_ids);
+                self.internal_refund_approval_expirations(&token_id, approval_expirations, &owner_id);
                 // The token is not owner by the receiver anymore. Can't return it.
                 return true;
             }
@@ -239,7 +315,8 @@ impl NonFungibleTokenResolver for Contract {
         //if there isn't a token object, it was burned and so we return true
         } else {
             //we refund the owner for releasing the storage used up by the approved account IDs
-            refund_approved_account_ids(owner_id, &approved_account_ids);
+            refund_approved_account_ids(owner_id.clone(), &approved_account_ids);
+            self.internal_refund_approval_expirations(&token_id, approval_expirations, &owner_id);
             return true;
         };
 
@@ -259,6 +336,25 @@ impl NonFungibleTokenResolver for Contract {
         //we inset the token back into the tokens_by_id collection
         self.tokens_by_id.insert(&token_id, &token);
 
+        //the expirations set by the receiver are dropped and the restored approvals get their expirations back
+        self.internal_prune_approval_expirations(&token_id, &receiver_id);
+        self.internal_restore_approval_expirations(&token_id, approval_expirations);
+        //approvals which expired during the transfer aren't restored, the owner gets back their storage
+        self.internal_purge_expired_approvals(&token_id);
+
+        //a user set by the receiver doesn't survive the revert
+        self.internal_clear_user(&token_id, &receiver_id);
+
+        //the revert is a part of the ownership history as well
+        self.internal_record_history(&token_id, ProvenanceEntry {
+            kind: ProvenanceKind::Revert,
+            from_id: Some(receiver_id.clone()),
+            to_id: owner_id.clone(),
+            authorized_id: authorized_id.as_ref().map(|authorized_id| authorized_id.parse().unwrap()),
+            price: None,
+            timestamp: U64(env::block_timestamp()),
+        });
+
         /*
             We need to log that the NFT was reverted back to the original owner.
             The old_owner_id will be the receiver and the new_owner_id will be the
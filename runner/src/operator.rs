@@ -0,0 +1,130 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //allow the operator to transfer all your tokens, current and future ones, without approving them one by one
+    #[payable]
+    pub fn nft_approve_all(&mut self, operator_id: AccountId) {
+        //assert at least one yocto for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+
+        let owner_id = env::predecessor_account_id();
+        assert_ne!(&owner_id, &operator_id, "The owner can't be an operator");
+
+        let initial_storage_usage = env::storage_usage();
+
+        let mut operators_set = self.operators_per_owner.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::OperatorsPerOwnerInner {
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        operators_set.insert(&operator_id);
+        self.operators_per_owner.insert(&owner_id, &operators_set);
+
+        //refund any excess storage attached by the user. If the user didn't attach enough, panic.
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        log_operators(EventLogVariant::NftApproveAll, &owner_id, vec![operator_id]);
+    }
+
+    //revoke a specific operator from transferring your tokens
+    #[payable]
+    pub fn nft_revoke_operator(&mut self, operator_id: AccountId) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+
+        let owner_id = env::predecessor_account_id();
+        let mut operators_set = self.operators_per_owner.get(&owner_id).expect("No operators");
+        assert!(operators_set.contains(&operator_id), "Not an operator");
+
+        let initial_storage_usage = env::storage_usage();
+
+        operators_set.remove(&operator_id);
+        if operators_set.is_empty() {
+            self.operators_per_owner.remove(&owner_id);
+        } else {
+            self.operators_per_owner.insert(&owner_id, &operators_set);
+        }
+
+        //refund the owner for releasing the storage used up by the operator
+        refund_released_storage(owner_id.clone(), initial_storage_usage - env::storage_usage());
+
+        log_operators(EventLogVariant::NftRevokeOperators, &owner_id, vec![operator_id]);
+    }
+
+    //revoke all operators from transferring your tokens
+    #[payable]
+    pub fn nft_revoke_all_operators(&mut self) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+
+        let owner_id = env::predecessor_account_id();
+        let mut operators_set = if let Some(operators_set) = self.operators_per_owner.get(&owner_id) {
+            operators_set
+        } else {
+            return;
+        };
+
+        let initial_storage_usage = env::storage_usage();
+
+        let operator_ids = operators_set.to_vec();
+        operators_set.clear();
+        self.operators_per_owner.remove(&owner_id);
+
+        //refund the owner for releasing the storage used up by the operators
+        refund_released_storage(owner_id.clone(), initial_storage_usage - env::storage_usage());
+
+        log_operators(EventLogVariant::NftRevokeOperators, &owner_id, operator_ids);
+    }
+
+    //check if the operator can transfer all the tokens of the owner
+    pub fn nft_is_approved_for_all(&self, owner_id: AccountId, operator_id: AccountId) -> bool {
+        self.internal_is_operator(&owner_id, &operator_id)
+    }
+
+    //get the operators of the owner using pagination
+    pub fn nft_operators_of(
+        &self,
+        owner_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<AccountId> {
+        let operators_set = if let Some(operators_set) = self.operators_per_owner.get(&owner_id) {
+            operators_set
+        } else {
+            return vec![];
+        };
+
+        let keys = operators_set.as_vector();
+        page_range(from_index, limit, keys.len())
+            .map(|index| keys.get(index).unwrap())
+            .collect()
+    }
+}
+
+impl Contract {
+    //check if the operator was approved for all the tokens of the owner
+    pub(crate) fn internal_is_operator(&self, owner_id: &AccountId, operator_id: &AccountId) -> bool {
+        self.operators_per_owner
+            .get(owner_id)
+            .is_some_and(|operators_set| operators_set.contains(operator_id))
+    }
+}
+
+//log the operators of the owner being approved or revoked as per the events standard
+fn log_operators(event: fn(Vec<NftOperatorLog>) -> EventLogVariant, owner_id: &AccountId, operator_ids: Vec<AccountId>) {
+    let nft_operator_log: EventLog = EventLog {
+        standard: RUNNER_STANDARD_NAME.to_string(),
+        version: RUNNER_EVENTS_VERSION.to_string(),
+        event: event(vec![NftOperatorLog {
+            owner_id: owner_id.to_string(),
+            operator_ids: operator_ids.iter().map(|operator_id| operator_id.to_string()).collect(),
+        }]),
+    };
+
+    env::log_str(&nft_operator_log.to_string());
+}
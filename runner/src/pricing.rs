@@ -0,0 +1,178 @@
+use crate::*;
+use crate::referral::assert_valid_referrer;
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+//max number of price tiers
+const MAX_PRICE_TIERS: usize = 10;
+const GAS_FOR_PARTNER_SUPPLY: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_PARTNER_MINT: Gas = Gas(30_000_000_000_000);
+
+//mint price for the holders of at least min_tokens tokens
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceTier {
+    pub min_tokens: u64,
+    pub price: U128,
+}
+
+//the pricing configuration returned from view calls
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PricingConfig {
+    pub mint_price: U128,
+    pub price_tiers: Vec<PriceTier>,
+    pub partner_contract_id: Option<AccountId>,
+}
+
+#[ext_contract(ext_partner_nft)]
+trait PartnerNft {
+    //NEP-181 number of tokens owned by the account
+    fn nft_supply_for_owner(&self, account_id: AccountId) -> U128;
+}
+
+/*
+    resolves the promise of the partner token count
+    this is stored on THIS contract and mints the token at the tiered price or refunds the buyer
+*/
+pub trait PartnerMintResolver {
+    fn nft_resolve_partner_mint(
+        &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
+        referrer_id: Option<AccountId>,
+        attached_deposit: U128,
+    ) -> Option<TokenId>;
+}
+
+#[near_bindgen]
+impl Contract {
+    //change the price tiers and the partner NFT contract. Tiers must be sorted by min_tokens
+    #[payable]
+    pub fn set_price_tiers(&mut self, price_tiers: Vec<PriceTier>, partner_contract_id: Option<AccountId>) {
+        assert_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        assert!(
+            price_tiers.len() <= MAX_PRICE_TIERS,
+            "Cannot set more than {} price tiers",
+            MAX_PRICE_TIERS
+        );
+        assert!(
+            price_tiers.windows(2).all(|tiers| tiers[0].min_tokens < tiers[1].min_tokens),
+            "Price tiers must be sorted by min_tokens"
+        );
+
+        self.price_tiers = price_tiers;
+        self.partner_contract_id = partner_contract_id;
+    }
+
+    //get the mint price, the price tiers and the partner NFT contract
+    pub fn get_pricing(&self) -> PricingConfig {
+        PricingConfig {
+            mint_price: self.mint_price,
+            price_tiers: self.price_tiers.clone(),
+            partner_contract_id: self.partner_contract_id.clone(),
+        }
+    }
+
+    //get the price nft_mint charges the account, only tokens of this contract are counted
+    pub fn get_mint_price_for(&self, account_id: AccountId) -> U128 {
+        self.internal_mint_price_for(&account_id, 0)
+    }
+
+    /*
+        mint the next token at the tiered price counting the tokens the buyer holds on the partner contract too.
+        The deposit must cover the price and the storage reserve of a token, otherwise it is refunded
+    */
+    #[payable]
+    pub fn nft_mint_partner(&mut self, receiver_id: AccountId, referrer_id: Option<AccountId>) -> Promise {
+        let partner_contract_id = self.partner_contract_id.clone().expect("No partner contract");
+        let buyer_id = env::predecessor_account_id();
+        assert!(near_sdk::env::block_timestamp() > self.mint_start.0, "Minting will start {}, now {}", self.mint_start.0, near_sdk::env::block_timestamp());
+        assert!(near_sdk::env::block_timestamp() < self.mint_end.0, "Minting is over {}, now {}", self.mint_end.0, near_sdk::env::block_timestamp());
+        assert!(self.nft_total_supply() < self.max_supply, "All tokens minted");
+        let storage_deposit = self.internal_mint_storage_deposit();
+        assert!(
+            env::attached_deposit() > storage_deposit,
+            "Must attach the mint price and {} yoctoNEAR for storage",
+            storage_deposit
+        );
+        if let Some(referrer_id) = referrer_id.as_ref() {
+            assert_valid_referrer(&buyer_id, referrer_id, &receiver_id);
+        }
+
+        ext_partner_nft::ext(partner_contract_id)
+            .with_static_gas(GAS_FOR_PARTNER_SUPPLY)
+            .nft_supply_for_owner(buyer_id.clone())
+        .then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_PARTNER_MINT)
+                .nft_resolve_partner_mint(buyer_id, receiver_id, referrer_id, U128(env::attached_deposit()))
+        )
+    }
+}
+
+#[near_bindgen]
+impl PartnerMintResolver for Contract {
+    //resolves the partner token count in nft_mint_partner
+    //returns the minted token ID or nothing if the deposit was refunded
+    #[private]
+    fn nft_resolve_partner_mint(
+        &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
+        referrer_id: Option<AccountId>,
+        attached_deposit: U128,
+    ) -> Option<TokenId> {
+        //a failed partner contract counts as no partner tokens
+        let partner_tokens = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U128>(&value)
+                .map(|supply| supply.0 as u64)
+                .unwrap_or(0),
+            _ => 0,
+        };
+        let price = self.internal_mint_price_for(&buyer_id, partner_tokens);
+
+        //the deposit is already on this contract, refund it instead of panicking
+        let now = env::block_timestamp();
+        if now <= self.mint_start.0
+            || now >= self.mint_end.0
+            || self.nft_total_supply() >= self.max_supply
+            || attached_deposit.0 < price.0 + self.internal_mint_storage_deposit()
+        {
+            Promise::new(buyer_id).transfer(attached_deposit.0);
+            return None;
+        }
+
+        let (token_id, required_cost) = self.internal_paid_mint(buyer_id.clone(), receiver_id, referrer_id, price);
+
+        //the storage reserve covers the storage of any token so the deposit is never short
+        let refund = attached_deposit.0.saturating_sub(required_cost);
+        if refund > 1 {
+            Promise::new(buyer_id).transfer(refund);
+        }
+        Some(token_id)
+    }
+}
+
+impl Contract {
+    //get the price of the tier of the account holding its tokens of this contract and the passed in partner tokens
+    pub(crate) fn internal_mint_price_for(&self, account_id: &AccountId, partner_tokens: u64) -> U128 {
+        let held_tokens = self
+            .tokens_per_owner
+            .get(account_id)
+            .map(|tokens| tokens.len())
+            .unwrap_or(0)
+            + partner_tokens;
+
+        self.price_tiers
+            .iter()
+            .rev()
+            .find(|tier| tier.min_tokens <= held_tokens)
+            .map(|tier| tier.price)
+            .unwrap_or(self.mint_price)
+    }
+}
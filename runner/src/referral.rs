@@ -0,0 +1,149 @@
+use crate::*;
+
+//number of referrers kept in the leaderboard
+const REFERRAL_LEADERBOARD_LEN: usize = 20;
+
+//referral stats of an account
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReferralStats {
+    //number of tokens minted with the account as the referrer
+    pub minted: u64,
+    //yoctoNEAR paid to the account as referral rewards
+    pub earned: U128,
+}
+
+impl Default for ReferralStats {
+    fn default() -> Self {
+        Self {
+            minted: 0,
+            earned: U128(0),
+        }
+    }
+}
+
+//a referrer and its stats returned from view calls
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Referrer {
+    pub referrer_id: AccountId,
+    pub stats: ReferralStats,
+}
+
+#[near_bindgen]
+impl Contract {
+    //change the share of the mint price paid to referrers, basis points (10000 is 100%)
+    #[payable]
+    pub fn set_referral_bps(&mut self, referral_bps: u32) {
+        assert_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        assert!(referral_bps <= 10000, "Referral bps must not be more than 10000");
+
+        self.referral_bps = referral_bps;
+    }
+
+    //get the share of the mint price paid to referrers, basis points
+    pub fn get_referral_bps(&self) -> u32 {
+        self.referral_bps
+    }
+
+    //get the referral stats of the account
+    pub fn referral_stats_of(&self, referrer_id: AccountId) -> ReferralStats {
+        self.referral_stats.get(&referrer_id).unwrap_or_default()
+    }
+
+    //get up to 20 referrers with the most minted tokens, the most earned NEAR breaks ties, using pagination
+    pub fn referral_leaderboard(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Referrer> {
+        let leaders = self.referral_leaders.get().unwrap_or_default();
+
+        page_range(from_index, limit, leaders.len() as u64)
+            .map(|index| leaders[index as usize].clone())
+            .collect()
+    }
+
+    //get all the referrers in the order of their first referral using pagination, for indexers which rank them
+    pub fn referrers(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Referrer> {
+        let keys = self.referral_stats.keys_as_vector();
+        let values = self.referral_stats.values_as_vector();
+
+        page_range(from_index, limit, keys.len())
+            .map(|index| Referrer {
+                referrer_id: keys.get(index).unwrap(),
+                stats: values.get(index).unwrap(),
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    //pay the referrer its share of the price of the token minted by the buyer and get the paid amount
+    pub(crate) fn internal_reward_referrer(
+        &mut self,
+        buyer_id: &AccountId,
+        referrer_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        price: U128,
+    ) -> Balance {
+        assert_valid_referrer(buyer_id, referrer_id, receiver_id);
+
+        let amount = price.0 * self.referral_bps as u128 / 10_000u128;
+
+        let mut stats = self.referral_stats.get(referrer_id).unwrap_or_default();
+        stats.minted += 1;
+        stats.earned = U128(stats.earned.0 + amount);
+        self.referral_stats.insert(referrer_id, &stats);
+        self.internal_update_referral_leaders(referrer_id, stats);
+
+        if amount > 0 {
+            Promise::new(referrer_id.clone()).transfer(amount);
+        }
+
+        let nft_referral_log: EventLog = EventLog {
+            standard: RUNNER_STANDARD_NAME.to_string(),
+            version: RUNNER_EVENTS_VERSION.to_string(),
+            event: EventLogVariant::NftReferral(vec![NftReferralLog {
+                referrer_id: referrer_id.to_string(),
+                buyer_id: buyer_id.to_string(),
+                token_ids: vec![token_id.to_string()],
+                amount: U128(amount),
+            }]),
+        };
+        env::log_str(&nft_referral_log.to_string());
+
+        amount
+    }
+
+    //move the referrer to its place in the leaderboard, the ones out of the top are dropped
+    fn internal_update_referral_leaders(&mut self, referrer_id: &AccountId, stats: ReferralStats) {
+        let mut leaders = self.referral_leaders.get().unwrap_or_default();
+        leaders.retain(|leader| &leader.referrer_id != referrer_id);
+
+        let position = leaders
+            .iter()
+            .position(|leader| {
+                (stats.minted, stats.earned.0) > (leader.stats.minted, leader.stats.earned.0)
+            })
+            .unwrap_or(leaders.len());
+        if position < REFERRAL_LEADERBOARD_LEN {
+            leaders.insert(position, Referrer {
+                referrer_id: referrer_id.clone(),
+                stats,
+            });
+            leaders.truncate(REFERRAL_LEADERBOARD_LEN);
+            self.referral_leaders.set(&leaders);
+        }
+    }
+}
+
+//make sure the referrer isn't the one who pays for the mint or gets the token
+pub(crate) fn assert_valid_referrer(buyer_id: &AccountId, referrer_id: &AccountId, receiver_id: &AccountId) {
+    assert!(
+        referrer_id != buyer_id && referrer_id != receiver_id,
+        "Referrer must not be the buyer"
+    );
+}
@@ -0,0 +1,109 @@
+use crate::*;
+
+//the account which has gameplay rights for the token until it expires. The owner doesn't change
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenUser {
+    //account which uses the token
+    pub user_id: AccountId,
+    //nanoseconds timestamp when the user loses access
+    pub expires: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    //lend the token to the user until expires (nanoseconds). Passing no user clears the current one
+    #[payable]
+    pub fn nft_set_user(&mut self, token_id: TokenId, user_id: Option<AccountId>, expires: U64) {
+        //assert at least one yocto for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+
+        //the owner, an operator or an approved account can set the user. Expired approvals are removed first
+        self.internal_purge_expired_approvals(&token_id);
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        let predecessor_account_id = env::predecessor_account_id();
+        assert!(
+            predecessor_account_id == token.owner_id
+                || self.internal_is_operator(&token.owner_id, &predecessor_account_id)
+                || token.approved_account_ids.contains_key(&predecessor_account_id),
+            "Predecessor must be the token owner or approved account."
+        );
+
+        let initial_storage_usage = env::storage_usage();
+
+        let expires = if let Some(user_id) = user_id.as_ref() {
+            assert!(
+                expires.0 > env::block_timestamp(),
+                "Expires {} must be in the future, now {}",
+                expires.0,
+                env::block_timestamp()
+            );
+            self.token_users.insert(&token_id, &TokenUser {
+                user_id: user_id.clone(),
+                expires,
+            });
+            expires
+        } else {
+            self.token_users.remove(&token_id);
+            U64(0)
+        };
+
+        //pay for the new storage with the attached deposit, any released storage is refunded to the owner
+        let final_storage_usage = env::storage_usage();
+        refund_deposit(final_storage_usage.saturating_sub(initial_storage_usage));
+        if initial_storage_usage > final_storage_usage {
+            refund_released_storage(token.owner_id, initial_storage_usage - final_storage_usage);
+        }
+
+        log_update_user(&token_id, user_id.as_ref(), expires);
+    }
+
+    //get the current user of the token. Nothing if the token isn't lent or the rental expired
+    pub fn nft_user_of(&self, token_id: TokenId) -> Option<AccountId> {
+        self.internal_active_user(&token_id).map(|user| user.user_id)
+    }
+
+    //get when the current user of the token loses access, 0 if there is no user
+    pub fn nft_user_expires(&self, token_id: TokenId) -> U64 {
+        self.internal_active_user(&token_id)
+            .map(|user| user.expires)
+            .unwrap_or(U64(0))
+    }
+}
+
+impl Contract {
+    //get the user of the token if the rental didn't expire yet
+    pub(crate) fn internal_active_user(&self, token_id: &TokenId) -> Option<TokenUser> {
+        self.token_users
+            .get(token_id)
+            .filter(|user| user.expires.0 > env::block_timestamp())
+    }
+
+    //remove the user of the token, expired or not, and refund the released storage to the passed in account ID
+    pub(crate) fn internal_clear_user(&mut self, token_id: &TokenId, refund_to: &AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        if let Some(user) = self.token_users.remove(token_id) {
+            refund_released_storage(refund_to.clone(), initial_storage_usage - env::storage_usage());
+
+            //only an active rental is worth an event, an expired one was already gone for the game
+            if user.expires.0 > env::block_timestamp() {
+                log_update_user(token_id, None, U64(0));
+            }
+        }
+    }
+}
+
+//log the change of the token user as per the events standard
+fn log_update_user(token_id: &TokenId, user_id: Option<&AccountId>, expires: U64) {
+    let nft_update_user_log: EventLog = EventLog {
+        standard: RUNNER_STANDARD_NAME.to_string(),
+        version: RUNNER_EVENTS_VERSION.to_string(),
+        event: EventLogVariant::NftUpdateUser(vec![NftUpdateUserLog {
+            token_id: token_id.to_string(),
+            user_id: user_id.map(|user_id| user_id.to_string()),
+            expires,
+        }]),
+    };
+
+    env::log_str(&nft_update_user_log.to_string());
+}
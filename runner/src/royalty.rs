@@ -24,39 +24,8 @@ impl NonFungibleTokenCore for Contract {
         //get the token object
 		let token = self.tokens_by_id.get(&token_id).expect("No token");
 
-        //get the owner of the token
-        let owner_id = token.owner_id;
-        //keep track of the total perpetual royalties
-        let mut total_perpetual = 0;
-        //get the u128 version of the passed in balance (which was U128 before)
-        let balance_u128 = u128::from(balance);
-		//keep track of the payout object to send back
-        let mut payout_object = Payout {
-            payout: HashMap::new()
-        };
-        //get the royalty object
-		let royalty = &self.perpetual_royalties;
-
-        //make sure we're not paying out to too many people (GAS limits this)
-		assert!(royalty.len() as u32 <= max_len_payout, "Market cannot payout to that many receivers");
-
-        //go through each key and value in the royalty object
-		for (k, v) in royalty.iter() {
-            //get the key
-			let key = k.clone();
-            //only insert into the payout if the key isn't the token owner (we add their payout at the end)
-			if key != owner_id {
-                //
-				payout_object.payout.insert(key, royalty_to_payout(*v, balance_u128));
-				total_perpetual += *v;
-			}
-		}
-
-		// payout to previous owner who gets 100% - total perpetual royalties
-		payout_object.payout.insert(owner_id, royalty_to_payout(10000 - total_perpetual, balance_u128));
-
-        //return the payout object
-		payout_object
+        //calculate the payout for the owner of the token
+        self.internal_payout(&token.owner_id, balance, max_len_payout)
 	}
 
     //transfers the token to the receiver ID and returns the payout object that should be payed given the passed in balance. 
@@ -88,39 +57,77 @@ impl NonFungibleTokenCore for Contract {
             previous_token.owner_id.clone(),
             &previous_token.approved_account_ids,
         );
+        self.internal_prune_approval_expirations(&token_id, &previous_token.owner_id);
+
+        //calculate the payout for the previous owner of the token
+        self.internal_payout(&previous_token.owner_id, balance, max_len_payout)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //get the royalty receivers of the token and their basis points (10000 is 100%), the owner isn't included
+    pub fn nft_royalty_info(&self, token_id: TokenId) -> HashMap<AccountId, u32> {
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+
+        self.internal_royalties(&token.owner_id).into_iter().collect()
+    }
+
+    //get the total royalty paid out of the sale price of the token, the rest goes to the owner
+    pub fn nft_royalty_amount(&self, token_id: TokenId, sale_price: U128) -> U128 {
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+
+        U128(
+            self.internal_royalties(&token.owner_id)
+                .into_iter()
+                .map(|(_, royalty)| royalty_to_payout(royalty, sale_price.0).0)
+                .sum(),
+        )
+    }
+}
+
+impl Contract {
+    //get the perpetual royalties which aren't paid to the owner, largest first and ties ordered by the account ID
+    pub(crate) fn internal_royalties(&self, owner_id: &AccountId) -> Vec<(AccountId, u32)> {
+        let mut royalties: Vec<(AccountId, u32)> = self
+            .perpetual_royalties
+            .iter()
+            .filter(|(account_id, _)| *account_id != owner_id)
+            .map(|(account_id, royalty)| (account_id.clone(), *royalty))
+            .collect();
+        royalties.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        royalties
+    }
+
+    /*
+        calculate the payout of the balance between the perpetual royalties and the owner.
+        if the market can't payout to that many receivers (GAS limits this) the smallest royalties
+        are folded into the owner's share, ties are broken by the account ID so the result is deterministic
+    */
+    pub(crate) fn internal_payout(&self, owner_id: &AccountId, balance: U128, max_len_payout: u32) -> Payout {
+        //the owner always takes one of the receivers
+        assert!(max_len_payout > 0, "Market must payout to at least one receiver");
+
+        let mut royalties = self.internal_royalties(owner_id);
+        royalties.truncate(max_len_payout as usize - 1);
 
-        //get the owner of the token
-        let owner_id = previous_token.owner_id;
-        //keep track of the total perpetual royalties
-        let mut total_perpetual = 0;
         //get the u128 version of the passed in balance (which was U128 before)
         let balance_u128 = u128::from(balance);
-		//keep track of the payout object to send back
+        //keep track of the total perpetual royalties
+        let mut total_perpetual = 0;
+        //keep track of the payout object to send back
         let mut payout_object = Payout {
             payout: HashMap::new()
         };
-        //get the royalty object
-		let royalty = self.perpetual_royalties.clone();
-
-        //make sure we're not paying out to too many people (GAS limits this)
-		assert!(royalty.len() as u32 <= max_len_payout, "Market cannot payout to that many receivers");
-
-        //go through each key and value in the royalty object
-		for (k, v) in royalty.iter() {
-            //get the key
-			let key = k.clone();
-            //only insert into the payout if the key isn't the token owner (we add their payout at the end)
-			if key != owner_id {
-                //
-				payout_object.payout.insert(key, royalty_to_payout(*v, balance_u128));
-				total_perpetual += *v;
-			}
-		}
-
-		// payout to previous owner who gets 100% - total perpetual royalties
-		payout_object.payout.insert(owner_id, royalty_to_payout(10000 - total_perpetual, balance_u128));
-
-        //return the payout object
-		payout_object
+
+        for (account_id, royalty) in royalties {
+            payout_object.payout.insert(account_id, royalty_to_payout(royalty, balance_u128));
+            total_perpetual += royalty;
+        }
+
+        // payout to previous owner who gets 100% - total perpetual royalties paid out
+        payout_object.payout.insert(owner_id.clone(), royalty_to_payout(10000 - total_perpetual, balance_u128));
+
+        payout_object
     }
-}
\ No newline at end of file
+}
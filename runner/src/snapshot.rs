@@ -0,0 +1,150 @@
+use crate::*;
+
+//number of tokens processed by one snapshot_process call if no limit is passed (GAS limits this)
+const DEFAULT_SNAPSHOT_PAGE: u64 = 100;
+
+//holders of the tokens at the moment of the snapshot. It is filled page by page with snapshot_process
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Snapshot {
+    //when the snapshot was created, nanoseconds
+    pub created_at: u64,
+    //when the last token was processed, nanoseconds
+    pub finished_at: Option<u64>,
+    //index of the next token to process in the tokens_by_id keys
+    pub next_index: u64,
+    //number of tokens owned by every holder
+    pub holders: UnorderedMap<AccountId, u64>,
+}
+
+//the snapshot returned from view calls
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonSnapshot {
+    pub snapshot_id: U64,
+    pub created_at: U64,
+    pub finished_at: Option<U64>,
+    //number of tokens processed so far
+    pub processed: U64,
+    pub holders_count: U64,
+}
+
+//a holder and the number of tokens owned at the moment of the snapshot
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SnapshotHolder {
+    pub account_id: AccountId,
+    pub token_count: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    //start a new snapshot of the holders and get its ID
+    #[payable]
+    pub fn snapshot_create(&mut self) -> U64 {
+        assert_at_least_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+
+        let initial_storage_usage = env::storage_usage();
+
+        let snapshot_id = self.next_snapshot_id;
+        self.next_snapshot_id += 1;
+        self.snapshots.insert(&snapshot_id, &Snapshot {
+            created_at: env::block_timestamp(),
+            finished_at: None,
+            next_index: 0,
+            holders: UnorderedMap::new(
+                StorageKey::SnapshotHolders { snapshot_id }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+        });
+
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        U64(snapshot_id)
+    }
+
+    /*
+        process the next "limit" tokens of the snapshot. Returns true when all the tokens are processed.
+        every page reflects the owners at the moment it was processed so the snapshot should be finished
+        within a short period of time.
+    */
+    #[payable]
+    pub fn snapshot_process(&mut self, snapshot_id: U64, limit: Option<u64>) -> bool {
+        assert_at_least_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+
+        let mut snapshot = self.snapshots.get(&snapshot_id.0).expect("No snapshot");
+        assert!(snapshot.finished_at.is_none(), "Snapshot is already finished");
+
+        let initial_storage_usage = env::storage_usage();
+
+        let keys = self.tokens_by_id.keys_as_vector();
+        let page = page_range(
+            Some(U128(snapshot.next_index as u128)),
+            Some(limit.unwrap_or(DEFAULT_SNAPSHOT_PAGE)),
+            keys.len(),
+        );
+
+        for index in page.clone() {
+            let token = self.tokens_by_id.get(&keys.get(index).unwrap()).unwrap();
+            let token_count = snapshot.holders.get(&token.owner_id).unwrap_or(0);
+            snapshot.holders.insert(&token.owner_id, &(token_count + 1));
+        }
+
+        snapshot.next_index = page.end;
+        if snapshot.next_index >= keys.len() {
+            snapshot.finished_at = Some(env::block_timestamp());
+        }
+        let finished = snapshot.finished_at.is_some();
+        self.snapshots.insert(&snapshot_id.0, &snapshot);
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+
+        finished
+    }
+
+    //get the progress of the snapshot
+    pub fn snapshot_info(&self, snapshot_id: U64) -> Option<JsonSnapshot> {
+        self.snapshots.get(&snapshot_id.0).map(|snapshot| JsonSnapshot {
+            snapshot_id,
+            created_at: U64(snapshot.created_at),
+            finished_at: snapshot.finished_at.map(U64),
+            processed: U64(snapshot.next_index),
+            holders_count: U64(snapshot.holders.len()),
+        })
+    }
+
+    //get the holders of the snapshot using pagination
+    pub fn snapshot_holders(
+        &self,
+        snapshot_id: U64,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<SnapshotHolder> {
+        let snapshot = self.snapshots.get(&snapshot_id.0).expect("No snapshot");
+        let keys = snapshot.holders.keys_as_vector();
+        let values = snapshot.holders.values_as_vector();
+
+        page_range(from_index, limit, keys.len())
+            .map(|index| SnapshotHolder {
+                account_id: keys.get(index).unwrap(),
+                token_count: U128(values.get(index).unwrap() as u128),
+            })
+            .collect()
+    }
+
+    //get the number of tokens the account held in the snapshot
+    pub fn snapshot_balance_of(&self, snapshot_id: U64, account_id: AccountId) -> U128 {
+        let snapshot = self.snapshots.get(&snapshot_id.0).expect("No snapshot");
+        U128(snapshot.holders.get(&account_id).unwrap_or(0) as u128)
+    }
+}
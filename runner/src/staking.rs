@@ -0,0 +1,295 @@
+use crate::*;
+
+//nanoseconds in one second, block timestamps are in nanoseconds
+const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+
+//keeps track of a staked token. The token stays with its owner but can't be transferred
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeInfo {
+    //owner of the token at the moment of staking
+    pub owner_id: AccountId,
+    //when the token was staked, nanoseconds
+    pub staked_at: U64,
+    //reward index at the last claim, rewards of the token are the growth of the index since then
+    pub reward_index: U128,
+}
+
+//the staking configuration returned from view calls
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakingConfig {
+    //reward points accrued per second for every staked token
+    pub reward_rate: U128,
+    //yoctoNEAR available to pay out rewards
+    pub reward_pool: U128,
+    //yoctoNEAR of the pool not owed to stakers, the owner can withdraw it
+    pub reward_pool_unallocated: U128,
+    //whether rewards are paid in NEAR (1 point is 1 yoctoNEAR) or recorded as points
+    pub rewards_in_near: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    //lock the token in place and start accruing rewards for it
+    #[payable]
+    pub fn stake(&mut self, token_id: TokenId) {
+        //assert at least one yocto for security reasons and to pay for storage
+        assert_at_least_one_yocto();
+
+        //get the token object and make sure that the caller is the owner
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        let owner_id = env::predecessor_account_id();
+        assert_eq!(&owner_id, &token.owner_id, "Predecessor must be the token owner.");
+        assert!(!self.staked_tokens.contains_key(&token_id), "Token is already staked");
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let reward_index = U128(self.internal_update_reward_index());
+        self.staked_tokens.insert(&token_id, &StakeInfo {
+            owner_id: owner_id.clone(),
+            staked_at: U64(env::block_timestamp()),
+            reward_index,
+        });
+        self.staked_tokens_count += 1;
+
+        //add the token to the set of staked tokens for the owner
+        let mut stakes_set = self.stakes_per_owner.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::StakesPerOwnerInner {
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        stakes_set.insert(&token_id);
+        self.stakes_per_owner.insert(&owner_id, &stakes_set);
+
+        //refund any excess storage attached by the user. If the user didn't attach enough, panic.
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+    }
+
+    //unlock the token. Rewards accrued so far are kept and can be claimed later
+    #[payable]
+    pub fn unstake(&mut self, token_id: TokenId) {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+
+        let stake = self.staked_tokens.get(&token_id).expect("Token is not staked");
+        let owner_id = env::predecessor_account_id();
+        assert_eq!(&owner_id, &stake.owner_id, "Predecessor must be the token owner.");
+
+        let initial_storage_usage = env::storage_usage();
+
+        //move the rewards of the token into the owner's pending rewards, they stay owed after the token stops accruing
+        self.internal_update_reward_index();
+        let accrued = self.internal_accrued_reward(&stake);
+        let pending = self.staking_rewards_pending.get(&owner_id).unwrap_or(0);
+        self.staking_rewards_pending.insert(&owner_id, &(pending + accrued));
+
+        self.staked_tokens.remove(&token_id);
+        self.staked_tokens_count -= 1;
+
+        let mut stakes_set = self.stakes_per_owner.get(&owner_id).expect("Token is not staked");
+        stakes_set.remove(&token_id);
+        if stakes_set.is_empty() {
+            self.stakes_per_owner.remove(&owner_id);
+        } else {
+            self.stakes_per_owner.insert(&owner_id, &stakes_set);
+        }
+
+        //refund the owner for releasing the storage used up by the stake
+        let final_storage_usage = env::storage_usage();
+        if final_storage_usage < initial_storage_usage {
+            refund_released_storage(owner_id, initial_storage_usage - final_storage_usage);
+        }
+    }
+
+    //claim all rewards of the caller. Paid in NEAR from the reward pool or recorded as reward points
+    #[payable]
+    pub fn claim(&mut self) -> U128 {
+        //assert that the user attached exactly 1 yoctoNEAR for security reasons
+        assert_one_yocto();
+
+        let account_id = env::predecessor_account_id();
+        let mut amount = self.staking_rewards_pending.remove(&account_id).unwrap_or(0);
+
+        //collect the rewards of every staked token and restart accrual from the current index
+        if let Some(stakes_set) = self.stakes_per_owner.get(&account_id) {
+            let reward_index = self.internal_update_reward_index();
+            for token_id in stakes_set.iter() {
+                let mut stake = self.staked_tokens.get(&token_id).unwrap();
+                amount += reward_index - stake.reward_index.0;
+                stake.reward_index = U128(reward_index);
+                self.staked_tokens.insert(&token_id, &stake);
+            }
+        }
+
+        if amount > 0 {
+            self.staking_rewards_owed = U128(self.staking_rewards_owed.0.saturating_sub(amount));
+            if self.staking_rewards_in_near {
+                //pay out from the funded pool, 1 point is 1 yoctoNEAR
+                assert!(
+                    amount <= self.staking_reward_pool.0,
+                    "Reward pool has {} yoctoNEAR, can't pay {}",
+                    self.staking_reward_pool.0,
+                    amount
+                );
+                self.staking_reward_pool = U128(self.staking_reward_pool.0 - amount);
+                Promise::new(account_id).transfer(amount);
+            } else {
+                //record the points so they can be converted into a fungible token later
+                let points = self.reward_points.get(&account_id).unwrap_or(0);
+                self.reward_points.insert(&account_id, &(points + amount));
+            }
+        }
+
+        U128(amount)
+    }
+
+    //add the attached deposit to the pool used to pay staking rewards in NEAR
+    #[payable]
+    pub fn fund_reward_pool(&mut self) -> U128 {
+        self.staking_reward_pool = U128(self.staking_reward_pool.0 + env::attached_deposit());
+        self.staking_reward_pool
+    }
+
+    //withdraw NEAR from the reward pool to the owner, only the part which isn't owed to stakers
+    #[payable]
+    pub fn withdraw_reward_pool(&mut self, amount: U128) -> U128 {
+        assert_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+
+        self.internal_update_reward_index();
+        let unallocated = self.staking_reward_pool.0.saturating_sub(self.staking_rewards_owed.0);
+        assert!(
+            amount.0 <= unallocated,
+            "Reward pool has {} yoctoNEAR not owed to stakers, can't withdraw {}",
+            unallocated,
+            amount.0
+        );
+
+        self.staking_reward_pool = U128(self.staking_reward_pool.0 - amount.0);
+        Promise::new(self.owner_id.clone()).transfer(amount.0);
+        self.staking_reward_pool
+    }
+
+    //change the reward points accrued per second per token and how the rewards are paid
+    #[payable]
+    pub fn set_staking_config(&mut self, reward_rate: U128, rewards_in_near: bool) {
+        assert_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+
+        //accrue the past seconds at the old rate before the new one applies
+        self.internal_update_reward_index();
+        self.staking_reward_rate = reward_rate;
+        self.staking_rewards_in_near = rewards_in_near;
+    }
+
+    //get the stake of the token if it is staked
+    pub fn staking_info(&self, token_id: TokenId) -> Option<StakeInfo> {
+        self.staked_tokens.get(&token_id)
+    }
+
+    //get the rewards accrued by the token since the last claim
+    pub fn staking_reward_of(&self, token_id: TokenId) -> U128 {
+        if let Some(stake) = self.staked_tokens.get(&token_id) {
+            U128(self.internal_accrued_reward(&stake))
+        } else {
+            U128(0)
+        }
+    }
+
+    //get all the rewards the account can claim right now
+    pub fn staking_rewards_for(&self, account_id: AccountId) -> U128 {
+        let mut amount = self.staking_rewards_pending.get(&account_id).unwrap_or(0);
+        if let Some(stakes_set) = self.stakes_per_owner.get(&account_id) {
+            for token_id in stakes_set.iter() {
+                amount += self.internal_accrued_reward(&self.staked_tokens.get(&token_id).unwrap());
+            }
+        }
+        U128(amount)
+    }
+
+    //get the reward points already claimed by the account
+    pub fn reward_points_of(&self, account_id: AccountId) -> U128 {
+        U128(self.reward_points.get(&account_id).unwrap_or(0))
+    }
+
+    //get the staked tokens of the account using pagination
+    pub fn staked_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenId> {
+        let stakes_set = if let Some(stakes_set) = self.stakes_per_owner.get(&account_id) {
+            stakes_set
+        } else {
+            return vec![];
+        };
+
+        let keys = stakes_set.as_vector();
+        page_range(from_index, limit, keys.len())
+            .map(|index| keys.get(index).unwrap())
+            .collect()
+    }
+
+    //get the current staking configuration
+    pub fn staking_config(&self) -> StakingConfig {
+        StakingConfig {
+            reward_rate: self.staking_reward_rate,
+            reward_pool: self.staking_reward_pool,
+            reward_pool_unallocated: U128(self.staking_reward_pool.0.saturating_sub(self.internal_rewards_owed())),
+            rewards_in_near: self.staking_rewards_in_near,
+        }
+    }
+}
+
+impl Contract {
+    //make sure the token isn't locked by staking
+    pub(crate) fn assert_not_staked(&self, token_id: &TokenId) {
+        assert!(!self.staked_tokens.contains_key(token_id), "Token is staked");
+    }
+
+    //calculate the rewards accrued by a stake since the last claim
+    pub(crate) fn internal_accrued_reward(&self, stake: &StakeInfo) -> u128 {
+        self.internal_reward_index(self.internal_unindexed_seconds()) - stake.reward_index.0
+    }
+
+    //get the whole seconds passed since the reward index was last updated
+    fn internal_unindexed_seconds(&self) -> u64 {
+        env::block_timestamp().saturating_sub(self.staking_index_updated_at.0) / NANOSECONDS_PER_SECOND
+    }
+
+    //get the reward index accrued for the given seconds after the last update at the current rate
+    fn internal_reward_index(&self, seconds: u64) -> u128 {
+        self.staking_reward_index.0 + seconds as u128 * self.staking_reward_rate.0
+    }
+
+    //get the rewards accrued by all the stakes up to the current block and not claimed yet
+    fn internal_rewards_owed(&self) -> u128 {
+        let seconds = self.internal_unindexed_seconds();
+        self.staking_rewards_owed.0
+            + (self.internal_reward_index(seconds) - self.staking_reward_index.0) * self.staked_tokens_count as u128
+    }
+
+    //accrue the reward index up to the current block, the fraction of a second is left for the next update
+    pub(crate) fn internal_update_reward_index(&mut self) -> u128 {
+        self.staking_rewards_owed = U128(self.internal_rewards_owed());
+        let seconds = self.internal_unindexed_seconds();
+        self.staking_reward_index = U128(self.internal_reward_index(seconds));
+        self.staking_index_updated_at = U64(self.staking_index_updated_at.0 + seconds * NANOSECONDS_PER_SECOND);
+        self.staking_reward_index.0
+    }
+}
@@ -0,0 +1,99 @@
+use crate::*;
+
+//data URL prefix of the composed SVG media, same as DATA_IMAGE_SVG_ICON
+const DATA_IMAGE_SVG_PREFIX: &str = "data:image/svg+xml,";
+
+#[near_bindgen]
+impl Contract {
+    /*
+        change the SVG layer drawn for the value of the trait category, nothing removes the layer.
+        A layer is the SVG markup placed inside the root svg element, layers are drawn in the trait table order
+    */
+    #[payable]
+    pub fn set_trait_layer(&mut self, category: String, value: String, layer: Option<String>) {
+        assert_at_least_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        self.assert_metadata_not_frozen();
+        let key = self.internal_trait_key(&category, &value).expect("No trait");
+
+        let initial_storage_usage = env::storage_usage();
+
+        if let Some(layer) = layer {
+            self.trait_layers.insert(&key, &layer);
+        } else {
+            self.trait_layers.remove(&key);
+        }
+
+        //pay for the new storage with the attached deposit, any released storage is refunded to the owner
+        let final_storage_usage = env::storage_usage();
+        refund_deposit(final_storage_usage.saturating_sub(initial_storage_usage));
+        if initial_storage_usage > final_storage_usage {
+            refund_released_storage(self.owner_id.clone(), initial_storage_usage - final_storage_usage);
+        }
+    }
+
+    //get the SVG layer drawn for the value of the trait category
+    pub fn get_trait_layer(&self, category: String, value: String) -> Option<String> {
+        self.internal_trait_key(&category, &value)
+            .and_then(|key| self.trait_layers.get(&key))
+    }
+
+    /*
+        serve the token media as an SVG data URL composed from the trait layers on-chain, so the art doesn't
+        depend on the base_uri gateway. Nothing switches back to the media from the token template
+    */
+    #[payable]
+    pub fn set_svg_media(&mut self, view_box: Option<String>) {
+        assert_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        self.assert_metadata_not_frozen();
+
+        self.svg_view_box = view_box;
+    }
+
+    //get the viewBox of the on-chain SVG media, nothing if the media is served by base_uri
+    pub fn get_svg_media(&self) -> Option<String> {
+        self.svg_view_box.clone()
+    }
+}
+
+impl Contract {
+    //compose the SVG of the token from the layers of its traits, nothing if the on-chain media is off or the token has no traits
+    pub(crate) fn internal_token_svg_media(&self, token_id: &TokenId) -> Option<String> {
+        let view_box = self.svg_view_box.as_ref()?;
+        let traits = self.token_traits.get(token_id)?;
+
+        let mut svg = format!("<svg xmlns='http://www.w3.org/2000/svg' viewBox='{}'>", view_box);
+        for (category_index, option_index) in traits.into_iter().enumerate() {
+            if let Some(layer) = self.trait_layers.get(&(category_index as u8, option_index)) {
+                svg.push_str(&layer);
+            }
+        }
+        svg.push_str("</svg>");
+
+        Some(format!("{}{}", DATA_IMAGE_SVG_PREFIX, escape_data_url(&svg)))
+    }
+}
+
+//percent-encode the characters which break an SVG data URL, the rest of the markup stays readable
+fn escape_data_url(svg: &str) -> String {
+    let mut escaped = String::with_capacity(svg.len());
+    for byte in svg.bytes() {
+        match byte {
+            b'%' | b'<' | b'>' | b'#' | b'"' | b'{' | b'}' | b'\n' | b'\r' | b'\t' => {
+                escaped.push_str(&format!("%{:02X}", byte))
+            }
+            byte if !byte.is_ascii() => escaped.push_str(&format!("%{:02X}", byte)),
+            byte => escaped.push(byte as char),
+        }
+    }
+    escaped
+}
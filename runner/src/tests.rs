@@ -1,10 +1,13 @@
 /* unit tests */
 #[cfg(test)]
 use crate::Contract;
-use crate::TokenMetadata;
+use crate::{MintVoucher, NFTContractMetadata, PriceTier, TokenMetadata, TokenMetadataTemplate};
+use crate::{NftDrop, TokenTrait, TraitCategory, TraitOption};
+use crate::{ProvenanceKind, MAX_HISTORY_LEN};
 use crate::approval::NonFungibleTokenCore;
+use near_sdk::json_types::Base64VecU8;
 use near_sdk::json_types::{U128, U64};
-use near_sdk::test_utils::{accounts, VMContextBuilder};
+use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
 use near_sdk::testing_env;
 use near_sdk::{env, AccountId};
 
@@ -12,6 +15,7 @@ use std::collections::HashMap;
 
 const MINT_STORAGE_COST: u128 = 100_000_000_000_000_000_000_000;
 const MIN_REQUIRED_APPROVAL_YOCTO: u128 = 170000000000000000000;
+const STAKE_STORAGE_COST: u128 = 10_000_000_000_000_000_000_000;
 const DEFAULT_BASE_URI: &str = "https://gateway.purecube.io/runner-testnet";
 const MINT_PRICE_NEAR: u128 = 5;
 const MINT_PRICE: U128 = U128(MINT_PRICE_NEAR * 1_000_000_000_000_000_000_000_000);
@@ -73,7 +77,7 @@ fn test_mint_nft() {
         .build());
 //     let token_metadata: TokenMetadata = sample_token_metadata();
     let token_id = "0".to_string();
-    contract.nft_mint(accounts(0));
+    contract.nft_mint(accounts(0), None);
     let contract_nft_tokens = contract.nft_tokens(Some(U128(0)), None);
     assert_eq!(contract_nft_tokens.len(), 1);
 
@@ -107,7 +111,7 @@ fn test_internal_transfer() {
         .block_timestamp(1680000000000000000)
         .build());
     let token_id = "0".to_string();
-    contract.nft_mint(accounts(0));
+    contract.nft_mint(accounts(0), None);
 
     testing_env!(context
         .storage_usage(env::storage_usage())
@@ -160,14 +164,14 @@ fn test_nft_approve() {
         .block_timestamp(1680000000000000000)
         .build());
     let token_id = "0".to_string();
-    contract.nft_mint(accounts(0));
+    contract.nft_mint(accounts(0), None);
 
     testing_env!(context
         .storage_usage(env::storage_usage())
         .attached_deposit(MIN_REQUIRED_APPROVAL_YOCTO)
         .predecessor_account_id(accounts(0))
         .build());
-    contract.nft_approve(token_id.clone(), accounts(1), None);
+    contract.nft_approve(token_id.clone(), accounts(1), None, None);
 
     testing_env!(context
         .storage_usage(env::storage_usage())
@@ -191,7 +195,7 @@ fn test_nft_revoke() {
         .block_timestamp(1680000000000000000)
         .build());
     let token_id = "0".to_string();
-    contract.nft_mint(accounts(0));
+    contract.nft_mint(accounts(0), None);
 
     // alice approves bob
     testing_env!(context
@@ -199,7 +203,7 @@ fn test_nft_revoke() {
         .attached_deposit(MIN_REQUIRED_APPROVAL_YOCTO)
         .predecessor_account_id(accounts(0))
         .build());
-    contract.nft_approve(token_id.clone(), accounts(1), None);
+    contract.nft_approve(token_id.clone(), accounts(1), None, None);
 
     // alice revokes bob
     testing_env!(context
@@ -229,7 +233,7 @@ fn test_revoke_all() {
         .predecessor_account_id(accounts(0))
         .block_timestamp(1680000000000000000)
         .build());
-    contract.nft_mint(accounts(0));
+    contract.nft_mint(accounts(0), None);
     let token_id: String = "0".to_string();
 
     // alice approves bob
@@ -238,7 +242,7 @@ fn test_revoke_all() {
         .attached_deposit(MIN_REQUIRED_APPROVAL_YOCTO)
         .predecessor_account_id(accounts(0))
         .build());
-    contract.nft_approve(token_id.clone(), accounts(1), None);
+    contract.nft_approve(token_id.clone(), accounts(1), None, None);
 
     // alice revokes bob
     testing_env!(context
@@ -268,7 +272,7 @@ fn test_internal_remove_token_from_owner() {
         .predecessor_account_id(accounts(0))
         .block_timestamp(1680000000000000000)
         .build());
-    contract.nft_mint(accounts(0));
+    contract.nft_mint(accounts(0), None);
     let token_id: String = "0".to_string();
 
     let contract_nft_tokens_before = contract.nft_tokens_for_owner(accounts(0), None, None);
@@ -292,7 +296,7 @@ fn test_nft_payout() {
         .predecessor_account_id(accounts(0))
         .block_timestamp(1680000000000000000)
         .build());
-    contract.nft_mint(accounts(0));
+    contract.nft_mint(accounts(0), None);
     let token_id: String = "0".to_string();
 
     // alice approves bob
@@ -301,7 +305,7 @@ fn test_nft_payout() {
         .attached_deposit(MIN_REQUIRED_APPROVAL_YOCTO)
         .predecessor_account_id(accounts(0))
         .build());
-    contract.nft_approve(token_id.clone(), accounts(1), None);
+    contract.nft_approve(token_id.clone(), accounts(1), None, None);
 
     let payout = contract.nft_payout(token_id.clone(), U128(10), 1);
     let expected = HashMap::from([(accounts(0), U128(10))]);
@@ -320,8 +324,2038 @@ fn test_nft_total_supply() {
         .predecessor_account_id(accounts(0))
         .block_timestamp(1680000000000000000)
         .build());
-    contract.nft_mint(accounts(0));
+    contract.nft_mint(accounts(0), None);
 
     let total_supply = contract.nft_total_supply();
     assert_eq!(total_supply, U128(1));
-}
\ No newline at end of file
+}
+#[test]
+fn test_stake_and_claim_points() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    let token_id: String = "0".to_string();
+
+    // owner sets 10 points per second
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_staking_config(U128(10), false);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.stake(token_id.clone());
+    assert!(contract.staking_info(token_id.clone()).is_some());
+
+    // 100 seconds later
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .block_timestamp(1680000100000000000)
+        .build());
+    assert_eq!(contract.staking_reward_of(token_id.clone()), U128(1000));
+    assert_eq!(contract.claim(), U128(1000));
+    assert_eq!(contract.reward_points_of(accounts(0)), U128(1000));
+    assert_eq!(contract.staking_rewards_for(accounts(0)), U128(0));
+
+    // rewards accrued before unstaking stay claimable
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .block_timestamp(1680000150000000000)
+        .build());
+    contract.unstake(token_id.clone());
+    assert!(contract.staking_info(token_id.clone()).is_none());
+    assert_eq!(contract.staking_rewards_for(accounts(0)), U128(500));
+}
+
+#[test]
+fn test_staking_rate_change() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    let token_id: String = "0".to_string();
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_staking_config(U128(10), false);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.stake(token_id.clone());
+
+    // the new rate only applies from now on
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .block_timestamp(1680000100000000000)
+        .build());
+    contract.set_staking_config(U128(20), false);
+    assert_eq!(contract.staking_reward_of(token_id.clone()), U128(1000));
+
+    // claiming in the middle of a second keeps the fraction for the next claim
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000150500000000)
+        .build());
+    assert_eq!(contract.claim(), U128(2000));
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .block_timestamp(1680000151000000000)
+        .build());
+    assert_eq!(contract.staking_reward_of(token_id), U128(20));
+}
+
+#[test]
+fn test_withdraw_reward_pool() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+
+    // 10 yoctoNEAR per second from a pool of 10000
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_staking_config(U128(10), true);
+    testing_env!(context.attached_deposit(10000).build());
+    contract.fund_reward_pool();
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.stake("0".to_string());
+
+    // 100 seconds later 1000 of the pool is owed to the staker
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .block_timestamp(1680000100000000000)
+        .build());
+    assert_eq!(contract.staking_config().reward_pool_unallocated, U128(9000));
+    assert_eq!(contract.withdraw_reward_pool(U128(9000)), U128(1000));
+    assert_eq!(contract.staking_config().reward_pool_unallocated, U128(0));
+
+    // the owed rewards stay claimable after unstaking
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.unstake("0".to_string());
+    assert_eq!(contract.claim(), U128(1000));
+    assert_eq!(contract.staking_config().reward_pool, U128(0));
+}
+
+#[test]
+#[should_panic(expected = "Reward pool has 9000 yoctoNEAR not owed to stakers, can't withdraw 9001")]
+fn test_withdraw_owed_reward_pool() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+
+    // 10 yoctoNEAR per second from a pool of 10000
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_staking_config(U128(10), true);
+    testing_env!(context.attached_deposit(10000).build());
+    contract.fund_reward_pool();
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.stake("0".to_string());
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .block_timestamp(1680000100000000000)
+        .build());
+    contract.withdraw_reward_pool(U128(9001));
+}
+
+#[test]
+#[should_panic(expected = "Token is staked")]
+fn test_transfer_staked_token() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    let token_id: String = "0".to_string();
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.stake(token_id.clone());
+
+    contract.internal_transfer(&accounts(0), &accounts(1), &token_id, None, None);
+}
+
+#[test]
+fn test_nft_set_user() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    let token_id: String = "0".to_string();
+
+    // alice lends the runner to charlie for 100 seconds
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_set_user(token_id.clone(), Some(accounts(2)), U64(1680000100000000000));
+    assert_eq!(contract.nft_user_of(token_id.clone()), Some(accounts(2)));
+    assert_eq!(contract.nft_user_expires(token_id.clone()), U64(1680000100000000000));
+
+    // the rental expires
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .block_timestamp(1680000100000000000)
+        .build());
+    assert_eq!(contract.nft_user_of(token_id.clone()), None);
+
+    // a transfer clears the user
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .block_timestamp(1680000200000000000)
+        .build());
+    contract.nft_set_user(token_id.clone(), Some(accounts(2)), U64(1680000300000000000));
+    contract.internal_transfer(&accounts(0), &accounts(1), &token_id, None, None);
+    assert_eq!(contract.nft_user_of(token_id.clone()), None);
+}
+
+#[test]
+fn test_nft_batch_transfer() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    for _ in 0..3 {
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(1680000000000000000)
+            .build());
+        contract.nft_mint(accounts(0), None);
+    }
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_batch_transfer(vec![
+        ("0".to_string(), accounts(1)),
+        ("1".to_string(), accounts(1)),
+        ("2".to_string(), accounts(2)),
+    ]);
+
+    assert_eq!(contract.nft_supply_for_owner(accounts(0)), U128(0));
+    assert_eq!(contract.nft_supply_for_owner(accounts(1)), U128(2));
+    assert_eq!(contract.nft_supply_for_owner(accounts(2)), U128(1));
+}
+
+#[test]
+fn test_nft_tokens_by_ids() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    contract.nft_mint(accounts(1), None);
+
+    let token_ids = vec!["1".to_string(), "7".to_string(), "0".to_string()];
+    let tokens = contract.nft_tokens_by_ids(token_ids.clone());
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens[0].as_ref().unwrap().owner_id, accounts(1));
+    assert!(tokens[1].is_none());
+    assert_eq!(tokens[2].as_ref().unwrap().metadata.title, sample_token_metadata().title);
+
+    let owners = contract.nft_owners_of(token_ids);
+    assert_eq!(owners, vec![Some(accounts(1)), None, Some(accounts(0))]);
+}
+
+#[test]
+fn test_nft_tokens_pagination_large_supply() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), U128(500), DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    // treasury mints 300 tokens, every third one to charlie
+    for token_index in 0..300 {
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(1680000000000000000)
+            .build());
+        let receiver_id = if token_index % 3 == 0 { accounts(2) } else { accounts(0) };
+        contract.nft_mint(receiver_id, None);
+    }
+
+    testing_env!(context.is_view(true).attached_deposit(0).build());
+    assert_eq!(contract.nft_total_supply(), U128(300));
+
+    let deep_page = contract.nft_tokens(Some(U128(250)), Some(20));
+    assert_eq!(deep_page.len(), 20);
+    assert_eq!(deep_page[0].token_id, "250".to_string());
+    assert_eq!(deep_page[19].token_id, "269".to_string());
+
+    let last_page = contract.nft_tokens(Some(U128(290)), Some(20));
+    assert_eq!(last_page.len(), 10);
+    assert_eq!(last_page[9].token_id, "299".to_string());
+    assert_eq!(contract.nft_tokens(Some(U128(300)), Some(20)).len(), 0);
+
+    let mut owner_token_ids: Vec<String> = vec![];
+    let mut from_index = 0;
+    loop {
+        let page = contract.nft_tokens_for_owner(accounts(2), Some(U128(from_index)), Some(30));
+        if page.is_empty() {
+            break;
+        }
+        from_index += page.len() as u128;
+        owner_token_ids.extend(page.into_iter().map(|token| token.token_id));
+    }
+    assert_eq!(owner_token_ids.len(), 100);
+    assert_eq!(owner_token_ids[99], "297".to_string());
+}
+
+#[test]
+fn test_nft_history() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    let token_id: String = "0".to_string();
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .block_timestamp(1680000100000000000)
+        .build());
+    contract.internal_transfer(&accounts(0), &accounts(1), &token_id, None, None);
+
+    let history = contract.nft_history(token_id.clone(), None, None);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].kind, ProvenanceKind::Mint);
+    assert_eq!(history[0].to_id, accounts(0));
+    assert_eq!(history[0].authorized_id, Some(accounts(0)));
+    assert_eq!(history[0].price, Some(MINT_PRICE));
+    assert_eq!(history[0].timestamp, U64(1680000000000000000));
+    assert_eq!(history[1].kind, ProvenanceKind::Transfer);
+    assert_eq!(history[1].from_id, Some(accounts(0)));
+    assert_eq!(history[1].to_id, accounts(1));
+    assert_eq!(history[1].timestamp, U64(1680000100000000000));
+
+    // the history is compacted keeping the mint entry
+    for transfer_index in 0..30 {
+        let (sender_id, receiver_id) = if transfer_index % 2 == 0 { (accounts(1), accounts(0)) } else { (accounts(0), accounts(1)) };
+        contract.internal_transfer(&sender_id, &receiver_id, &token_id, None, None);
+    }
+    let history = contract.nft_history(token_id.clone(), None, Some(100));
+    assert_eq!(history.len(), MAX_HISTORY_LEN);
+    assert_eq!(history[0].kind, ProvenanceKind::Mint);
+    assert_eq!(history[MAX_HISTORY_LEN - 1].to_id, accounts(1));
+
+    let page = contract.nft_history(token_id.clone(), Some(U128(MAX_HISTORY_LEN as u128 - 2)), Some(5));
+    assert_eq!(page.len(), 2);
+}
+
+#[test]
+fn test_history_storage_reserve() {
+    use crate::HISTORY_STORAGE_RESERVE;
+    use near_sdk::borsh::BorshSerialize;
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    let token_id: String = "0".to_string();
+    let history_len = |contract: &Contract| contract.token_history.get(&token_id).unwrap().try_to_vec().unwrap().len() as u64;
+    let minted_len = history_len(&contract);
+
+    // a full history of transfers between the longest account IDs fits in the reserve paid at mint
+    let first: AccountId = "a".repeat(64).parse().unwrap();
+    let second: AccountId = "b".repeat(64).parse().unwrap();
+    contract.internal_transfer(&accounts(0), &first, &token_id, None, None);
+    for transfer_index in 0..MAX_HISTORY_LEN {
+        let (sender_id, receiver_id) = if transfer_index % 2 == 0 { (&first, &second) } else { (&second, &first) };
+        contract.internal_transfer(sender_id, receiver_id, &token_id, None, None);
+    }
+    assert!(history_len(&contract) - minted_len <= HISTORY_STORAGE_RESERVE);
+}
+
+#[test]
+fn test_snapshot() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    for token_index in 0..5 {
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(1680000000000000000)
+            .build());
+        let receiver_id = if token_index < 3 { accounts(0) } else { accounts(3) };
+        contract.nft_mint(receiver_id, None);
+    }
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    let snapshot_id = contract.snapshot_create();
+    assert!(!contract.snapshot_process(snapshot_id, Some(2)));
+    assert!(!contract.snapshot_process(snapshot_id, Some(2)));
+    assert!(contract.snapshot_process(snapshot_id, Some(2)));
+
+    let info = contract.snapshot_info(snapshot_id).unwrap();
+    assert_eq!(info.processed, U64(5));
+    assert_eq!(info.holders_count, U64(2));
+    assert!(info.finished_at.is_some());
+
+    let holders = contract.snapshot_holders(snapshot_id, None, None);
+    assert_eq!(holders.len(), 2);
+    assert_eq!(holders[0].account_id, accounts(0));
+    assert_eq!(holders[0].token_count, U128(3));
+    assert_eq!(contract.snapshot_balance_of(snapshot_id, accounts(3)), U128(2));
+}
+
+#[test]
+fn test_nft_airdrop() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_airdrop(vec![accounts(0), accounts(3), accounts(0)]);
+
+    assert_eq!(contract.nft_total_supply(), U128(3));
+    assert_eq!(contract.nft_supply_for_owner(accounts(0)), U128(2));
+    assert_eq!(contract.nft_owners_of(vec!["1".to_string()]), vec![Some(accounts(3))]);
+    assert_eq!(contract.nft_history("2".to_string(), None, None)[0].price, Some(U128(0)));
+}
+
+#[test]
+#[should_panic(expected = "Only 1 tokens left to mint")]
+fn test_nft_airdrop_over_max_supply() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), U128(1), DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0), accounts(3)]);
+}
+
+#[test]
+#[should_panic(expected = "Max supply must not be less then 2 minted tokens")]
+fn test_set_meta_max_supply_below_minted() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0), accounts(3)]);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_meta("Chubby Runners".to_string(), DEFAULT_BASE_URI.to_string(), None, U128(1));
+}
+
+#[test]
+#[should_panic(expected = "Supply is frozen")]
+fn test_freeze_supply() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_meta("Chubby Runners".to_string(), DEFAULT_BASE_URI.to_string(), None, U128(20));
+    contract.freeze_supply();
+    assert!(contract.supply_frozen());
+
+    // the same cap is still accepted, a new one is not
+    contract.set_meta("Chubby Runners".to_string(), DEFAULT_BASE_URI.to_string(), None, U128(20));
+    contract.set_meta("Chubby Runners".to_string(), DEFAULT_BASE_URI.to_string(), None, U128(30));
+}
+
+#[test]
+fn test_freeze_token_metadata() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0), accounts(3)]);
+
+    let media_hash = Base64VecU8(vec![1; 32]);
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.freeze_token_metadata("0".to_string(), Some(media_hash.clone()), Some(Base64VecU8(vec![2; 32])));
+    assert!(contract.token_metadata_frozen("0".to_string()));
+    assert!(!contract.token_metadata_frozen("1".to_string()));
+
+    // the frozen token doesn't depend on the gateway anymore
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .build());
+    contract.set_meta("Chubby Runners".to_string(), "https://new.gateway/".to_string(), None, MAX_SUPPLY);
+    let tokens = contract.nft_tokens_by_ids(vec!["0".to_string(), "1".to_string()]);
+    let frozen_metadata = &tokens[0].as_ref().unwrap().metadata;
+    assert_eq!(frozen_metadata.media, Some(format!("{}/img/0.png", DEFAULT_BASE_URI)));
+    assert_eq!(frozen_metadata.media_hash, Some(media_hash));
+    assert_eq!(tokens[1].as_ref().unwrap().metadata.media, Some("img/1.png".to_string()));
+
+    // the generated metadata can be frozen without the hashes
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .build());
+    contract.freeze_token_metadata("1".to_string(), None, None);
+    let frozen_metadata = contract.nft_tokens_by_ids(vec!["1".to_string()]).pop().unwrap().unwrap().metadata;
+    assert_eq!(frozen_metadata.media, Some("https://new.gateway/img/1.png".to_string()));
+    assert_eq!(frozen_metadata.media_hash, None);
+}
+
+#[test]
+#[should_panic(expected = "Metadata is frozen")]
+fn test_freeze_metadata() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.freeze_metadata();
+    assert!(contract.metadata_frozen());
+
+    // the max supply can still change
+    contract.set_meta("Chubby Runners".to_string(), DEFAULT_BASE_URI.to_string(), None, U128(MAX_SUPPLY.0 + 1));
+    assert_eq!(contract.max_supply, U128(MAX_SUPPLY.0 + 1));
+
+    contract.set_meta("Chubby Runners".to_string(), "https://new.gateway".to_string(), None, MAX_SUPPLY);
+}
+
+#[test]
+fn test_token_template() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+    assert_eq!(contract.get_token_template(), TokenMetadataTemplate::default());
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0)]);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_token_template(TokenMetadataTemplate {
+        title: Some("Space Runner {id}".to_string()),
+        description: None,
+        media: Some("media/{id}/image.webp".to_string()),
+        reference: Some("meta/{id}.json".to_string()),
+    });
+
+    let tokens = contract.nft_tokens_by_ids(vec!["0".to_string()]);
+    let metadata = &tokens[0].as_ref().unwrap().metadata;
+    assert_eq!(metadata.title, Some("Space Runner 0".to_string()));
+    assert_eq!(metadata.description, None);
+    assert_eq!(metadata.media, Some("media/0/image.webp".to_string()));
+    assert_eq!(metadata.reference, Some("meta/0.json".to_string()));
+}
+
+#[test]
+fn test_nft_payout_folds_smallest_royalties() {
+    use crate::royalty::NonFungibleTokenCore;
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let royalties = HashMap::from([(accounts(3), 500), (accounts(4), 300), (accounts(5), 300)]);
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, Some(royalties));
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0)]);
+    let token_id: String = "0".to_string();
+
+    // every receiver fits
+    let payout = contract.nft_payout(token_id.clone(), U128(10000), 4);
+    assert_eq!(payout.payout.len(), 4);
+    assert_eq!(payout.payout.get(&accounts(0)), Some(&U128(8900)));
+
+    // the smallest royalty goes to the owner, equal royalties are ordered by the account ID
+    let payout = contract.nft_payout(token_id.clone(), U128(10000), 3);
+    let expected = HashMap::from([(accounts(0), U128(9200)), (accounts(3), U128(500)), (accounts(4), U128(300))]);
+    assert_eq!(payout.payout, expected);
+
+    // only the owner
+    let payout = contract.nft_payout(token_id.clone(), U128(10000), 1);
+    assert_eq!(payout.payout, HashMap::from([(accounts(0), U128(10000))]));
+}
+
+#[test]
+fn test_nft_royalty_info() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let royalties = HashMap::from([(accounts(3), 500), (accounts(4), 300), (accounts(5), 300)]);
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, Some(royalties));
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0)]);
+    let token_id: String = "0".to_string();
+
+    // the royalties of a sale without a payout
+    let royalty_info = contract.nft_royalty_info(token_id.clone());
+    assert_eq!(royalty_info.len(), 3);
+    assert_eq!(royalty_info.get(&accounts(3)), Some(&500));
+    assert_eq!(contract.nft_royalty_amount(token_id.clone(), U128(10000)), U128(1100));
+    assert_eq!(contract.nft_royalty_amount(token_id, U128(0)), U128(0));
+}
+
+#[test]
+fn test_nft_approve_with_expiry() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    let token_id = "0".to_string();
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_approve_with_expiry(token_id.clone(), accounts(1), U64(1680000100000000000), None, None);
+    contract.nft_approve(token_id.clone(), accounts(3), None, None);
+    assert!(contract.nft_is_approved(token_id.clone(), accounts(1), Some(0)));
+    assert_eq!(contract.nft_approval_expires_at(token_id.clone(), accounts(1)), Some(U64(1680000100000000000)));
+    assert_eq!(contract.nft_approval_expires_at(token_id.clone(), accounts(3)), None);
+
+    // the expired approval is ignored and removed with the next approval
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .block_timestamp(1680000100000000000)
+        .build());
+    assert!(!contract.nft_is_approved(token_id.clone(), accounts(1), None));
+    assert!(contract.nft_is_approved(token_id.clone(), accounts(3), None));
+    contract.nft_approve(token_id.clone(), accounts(4), None, None);
+    let token = contract.nft_tokens_by_ids(vec![token_id.clone()]).pop().unwrap().unwrap();
+    assert_eq!(token.approved_account_ids.len(), 2);
+    assert!(!token.approved_account_ids.contains_key(&accounts(1)));
+    assert_eq!(contract.nft_approval_expires_at(token_id, accounts(1)), None);
+}
+
+#[test]
+fn test_nft_resolve_transfer_keeps_staked_token() {
+    use crate::nft_core::{NonFungibleTokenCore as _, NonFungibleTokenResolver};
+    use crate::PreviousApprovals;
+    use near_sdk::{PromiseResult, RuntimeFeesConfig, VMConfig};
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    let token_id = "0".to_string();
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_transfer_call(accounts(3), token_id.clone(), None, None, "".to_string());
+
+    // the receiver stakes the token during nft_on_transfer
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(3))
+        .build());
+    contract.stake(token_id.clone());
+
+    // the receiver asks to return the token, the staked token stays with the receiver
+    testing_env!(
+        context.storage_usage(env::storage_usage()).attached_deposit(0).predecessor_account_id(accounts(0)).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(b"true".to_vec())],
+    );
+    let previous_approvals = PreviousApprovals { approved_account_ids: HashMap::new(), expirations: HashMap::new() };
+    assert!(contract.nft_resolve_transfer(None, accounts(0), accounts(3), token_id.clone(), previous_approvals, None));
+    assert_eq!(contract.nft_owners_of(vec![token_id.clone()]), vec![Some(accounts(3))]);
+    assert_eq!(contract.staking_info(token_id).unwrap().owner_id, accounts(3));
+}
+
+#[test]
+fn test_nft_resolve_transfer_restores_expirations() {
+    use crate::nft_core::{NonFungibleTokenCore as _, NonFungibleTokenResolver};
+    use crate::PreviousApprovals;
+    use near_sdk::{PromiseResult, RuntimeFeesConfig, VMConfig};
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    let token_id = "0".to_string();
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_approve_with_expiry(token_id.clone(), accounts(1), U64(1680000100000000000), None, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_transfer_call(accounts(3), token_id.clone(), None, None, "".to_string());
+
+    // the receiver approves an account during nft_on_transfer
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(3))
+        .build());
+    contract.nft_approve_with_expiry(token_id.clone(), accounts(4), U64(1680000200000000000), None, None);
+
+    // the receiver returns the token, the previous approvals keep their expirations
+    testing_env!(
+        context.storage_usage(env::storage_usage()).attached_deposit(0).predecessor_account_id(accounts(0)).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(b"true".to_vec())],
+    );
+    let previous_approvals = PreviousApprovals {
+        approved_account_ids: HashMap::from([(accounts(1), 0)]),
+        expirations: HashMap::from([(0, 1680000100000000000)]),
+    };
+    assert!(!contract.nft_resolve_transfer(None, accounts(0), accounts(3), token_id.clone(), previous_approvals, None));
+    assert_eq!(contract.nft_owners_of(vec![token_id.clone()]), vec![Some(accounts(0))]);
+    assert!(contract.nft_is_approved(token_id.clone(), accounts(1), Some(0)));
+    assert!(!contract.nft_is_approved(token_id.clone(), accounts(4), None));
+    assert_eq!(contract.nft_approval_expires_at(token_id, accounts(1)), Some(U64(1680000100000000000)));
+}
+
+#[test]
+fn test_revoke_removes_expired_approvals() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    let token_id = "0".to_string();
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_approve_with_expiry(token_id.clone(), accounts(1), U64(1680000100000000000), None, None);
+    contract.nft_approve_with_expiry(token_id.clone(), accounts(3), U64(1680000100000000000), None, None);
+    contract.nft_approve(token_id.clone(), accounts(4), None, None);
+
+    // revoking an approval also removes the expired ones and refunds the owner
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .block_timestamp(1680000100000000000)
+        .build());
+    contract.nft_revoke(token_id.clone(), accounts(3));
+    let token = contract.nft_tokens_by_ids(vec![token_id.clone()]).pop().unwrap().unwrap();
+    assert_eq!(token.approved_account_ids, HashMap::from([(accounts(4), 2)]));
+    assert!(contract.approval_expirations.get(&token_id).is_none());
+    assert!(get_created_receipts().iter().all(|receipt| receipt.receiver_id == accounts(0)));
+}
+
+#[test]
+fn test_nft_remove_expired_approvals() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    let token_id = "0".to_string();
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_approve_with_expiry(token_id.clone(), accounts(1), U64(1680000100000000000), None, None);
+    contract.nft_approve(token_id.clone(), accounts(3), None, None);
+
+    // anyone can remove the expired approvals, the owner gets back the storage
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(0)
+        .predecessor_account_id(accounts(5))
+        .block_timestamp(1680000100000000000)
+        .build());
+    contract.nft_remove_expired_approvals(token_id.clone());
+    let token = contract.nft_tokens_by_ids(vec![token_id.clone()]).pop().unwrap().unwrap();
+    assert_eq!(token.approved_account_ids, HashMap::from([(accounts(3), 1)]));
+    assert!(contract.approval_expirations.get(&token_id).is_none());
+    let receipts = get_created_receipts();
+    assert!(!receipts.is_empty());
+    assert!(receipts.iter().all(|receipt| receipt.receiver_id == accounts(0)));
+}
+
+#[test]
+fn test_nft_resolve_transfer_drops_expired_approvals() {
+    use crate::nft_core::{NonFungibleTokenCore as _, NonFungibleTokenResolver};
+    use crate::PreviousApprovals;
+    use near_sdk::{PromiseResult, RuntimeFeesConfig, VMConfig};
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    let token_id = "0".to_string();
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_approve_with_expiry(token_id.clone(), accounts(1), U64(1680000100000000000), None, None);
+    contract.nft_approve(token_id.clone(), accounts(3), None, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_transfer_call(accounts(4), token_id.clone(), None, None, "".to_string());
+
+    // the token comes back after the approval expired, only the active approval is restored
+    testing_env!(
+        context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(0)
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(1680000100000000000)
+            .build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(b"true".to_vec())],
+    );
+    let previous_approvals = PreviousApprovals {
+        approved_account_ids: HashMap::from([(accounts(1), 0), (accounts(3), 1)]),
+        expirations: HashMap::from([(0, 1680000100000000000)]),
+    };
+    assert!(!contract.nft_resolve_transfer(None, accounts(0), accounts(4), token_id.clone(), previous_approvals, None));
+    let token = contract.nft_tokens_by_ids(vec![token_id.clone()]).pop().unwrap().unwrap();
+    assert_eq!(token.approved_account_ids, HashMap::from([(accounts(3), 1)]));
+    assert!(contract.approval_expirations.get(&token_id).is_none());
+}
+
+#[test]
+#[should_panic(expected = "Approval expired")]
+fn test_transfer_with_expired_approval() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    let token_id = "0".to_string();
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_approve_with_expiry(token_id.clone(), accounts(1), U64(1680000100000000000), None, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .block_timestamp(1680000200000000000)
+        .build());
+    contract.internal_transfer(&accounts(1), &accounts(3), &token_id, None, None);
+}
+
+#[test]
+fn test_nft_approve_all() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0), accounts(0)]);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(STAKE_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_approve_all(accounts(1));
+    contract.nft_approve_all(accounts(3));
+    assert!(contract.nft_is_approved_for_all(accounts(0), accounts(1)));
+    assert!(!contract.nft_is_approved_for_all(accounts(3), accounts(1)));
+    assert_eq!(contract.nft_operators_of(accounts(0), None, None), vec![accounts(1), accounts(3)]);
+
+    // the operator transfers a token without a per-token approval
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.internal_transfer(&accounts(1), &accounts(4), &"0".to_string(), None, None);
+    assert_eq!(contract.nft_owners_of(vec!["0".to_string()]), vec![Some(accounts(4))]);
+    assert!(get_logs().iter().any(|log| log.contains("\"authorized_id\":\"bob\"")));
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_revoke_operator(accounts(3));
+    assert_eq!(contract.nft_operators_of(accounts(0), None, None), vec![accounts(1)]);
+    contract.nft_revoke_all_operators();
+    assert!(!contract.nft_is_approved_for_all(accounts(0), accounts(1)));
+}
+
+#[test]
+fn test_nft_resolve_approve_failed() {
+    use crate::approval::NonFungibleTokenApprovalResolver;
+    use near_sdk::{PromiseResult, RuntimeFeesConfig, VMConfig};
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0)]);
+    let token_id = "0".to_string();
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MIN_REQUIRED_APPROVAL_YOCTO)
+        .predecessor_account_id(accounts(0))
+        .build());
+    contract.nft_approve(token_id.clone(), accounts(1), None, None);
+    contract.nft_approve(token_id.clone(), accounts(3), None, None);
+
+    // the receiver accepted the approval
+    testing_env!(
+        context.storage_usage(env::storage_usage()).attached_deposit(0).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(vec![])],
+    );
+    assert!(contract.nft_resolve_approve(accounts(0), token_id.clone(), accounts(3), 1));
+
+    // the receiver panicked, the approval is rolled back
+    testing_env!(
+        context.storage_usage(env::storage_usage()).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Failed],
+    );
+    assert!(!contract.nft_resolve_approve(accounts(0), token_id.clone(), accounts(1), 0));
+    assert!(!contract.nft_is_approved(token_id.clone(), accounts(1), None));
+    assert!(contract.nft_is_approved(token_id, accounts(3), Some(1)));
+}
+
+fn sign_voucher(voucher: &MintVoucher) -> Base64VecU8 {
+    use ed25519_dalek::Signer;
+    let secret = ed25519_dalek::SecretKey::from_bytes(&[7; 32]).unwrap();
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    let keypair = ed25519_dalek::Keypair { secret, public };
+    Base64VecU8(keypair.sign(&voucher.message(&env::current_account_id())).to_bytes().to_vec())
+}
+
+fn voucher_signer() -> near_sdk::PublicKey {
+    let secret = ed25519_dalek::SecretKey::from_bytes(&[7; 32]).unwrap();
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    near_sdk::PublicKey::try_from([vec![0], public.to_bytes().to_vec()].concat()).unwrap()
+}
+
+#[test]
+fn test_nft_mint_with_voucher() {
+    use near_sdk::mock::VmAction;
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_voucher_signer(Some(voucher_signer()));
+
+    let voucher = MintVoucher {
+        receiver_id: accounts(3),
+        token_type: None,
+        price: MINT_PRICE,
+        nonce: U64(42),
+        expires_at: U64(1680000100000000000),
+    };
+    let signature = sign_voucher(&voucher);
+
+    // anyone can redeem the voucher for the receiver, paying the voucher price and storage
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(4))
+        .block_timestamp(1680000000000000000)
+        .build());
+    assert!(!contract.voucher_used(U64(42)));
+    contract.nft_mint_with_voucher(voucher, signature);
+    assert!(contract.voucher_used(U64(42)));
+    assert_eq!(contract.nft_owners_of(vec!["0".to_string()]), vec![Some(accounts(3))]);
+
+    // the price is paid to the treasury
+    let receipts = get_created_receipts();
+    assert!(receipts.iter().any(|receipt| receipt.receiver_id == accounts(2)
+        && receipt.actions == vec![VmAction::Transfer { deposit: MINT_PRICE.0 }]));
+}
+
+#[test]
+#[should_panic(expected = "Voucher is already used")]
+fn test_nft_mint_with_used_voucher() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_voucher_signer(Some(voucher_signer()));
+
+    let voucher = MintVoucher {
+        receiver_id: accounts(3),
+        token_type: None,
+        price: U128(0),
+        nonce: U64(42),
+        expires_at: U64(1680000100000000000),
+    };
+    let signature = sign_voucher(&voucher);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(3))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint_with_voucher(voucher.clone(), signature.clone());
+
+    // the same voucher can't be redeemed twice
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .build());
+    contract.nft_mint_with_voucher(voucher, signature);
+}
+
+#[test]
+#[should_panic(expected = "Invalid signature")]
+fn test_nft_mint_with_forged_voucher() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_voucher_signer(Some(voucher_signer()));
+
+    let mut voucher = MintVoucher {
+        receiver_id: accounts(3),
+        token_type: None,
+        price: MINT_PRICE,
+        nonce: U64(1),
+        expires_at: U64(1680000100000000000),
+    };
+    let signature = sign_voucher(&voucher);
+
+    // the price was changed after signing
+    voucher.price = U128(0);
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(3))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint_with_voucher(voucher, signature);
+}
+
+#[test]
+fn test_nft_mint_with_voucher_token_type() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(sample_trait_table());
+    testing_env!(context.storage_usage(env::storage_usage()).attached_deposit(1).build());
+    contract.set_voucher_signer(Some(voucher_signer()));
+
+    // the blue background has no weight, only a voucher can mint it
+    let token_type = TokenTrait { trait_type: "Background".to_string(), value: "Blue".to_string() };
+    let voucher = MintVoucher {
+        receiver_id: accounts(3),
+        token_type: Some(token_type.clone()),
+        price: U128(0),
+        nonce: U64(1),
+        expires_at: U64(1680000100000000000),
+    };
+    let signature = sign_voucher(&voucher);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(3))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint_with_voucher(voucher, signature);
+
+    let traits = contract.nft_traits("0".to_string());
+    assert_eq!(traits[0], token_type);
+    assert_eq!(traits[1], TokenTrait { trait_type: "Hat".to_string(), value: "Cap".to_string() });
+}
+
+#[test]
+#[should_panic(expected = "Unknown token type")]
+fn test_nft_mint_with_voucher_unknown_token_type() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(sample_trait_table());
+    testing_env!(context.storage_usage(env::storage_usage()).attached_deposit(1).build());
+    contract.set_voucher_signer(Some(voucher_signer()));
+
+    let voucher = MintVoucher {
+        receiver_id: accounts(3),
+        token_type: Some(TokenTrait { trait_type: "Background".to_string(), value: "Red".to_string() }),
+        price: U128(0),
+        nonce: U64(1),
+        expires_at: U64(1680000100000000000),
+    };
+    let signature = sign_voucher(&voucher);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(3))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint_with_voucher(voucher, signature);
+}
+
+#[test]
+fn test_nft_claim() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let code_hashes: Vec<Base64VecU8> = ["first secret", "second secret", "third secret", "fourth secret"]
+        .iter()
+        .map(|secret| Base64VecU8(env::sha256(secret.as_bytes())))
+        .collect();
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(4 * MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .block_timestamp(1680000000000000000)
+        .build());
+    // every code has its own expiry, the last one never expires
+    let expires_at = Some(U64(1680000100000000000));
+    contract.register_claim_codes(vec![
+        (code_hashes[0].clone(), expires_at),
+        (code_hashes[1].clone(), expires_at),
+        (code_hashes[2].clone(), expires_at),
+        (code_hashes[3].clone(), None),
+    ]);
+    assert_eq!(contract.claim_codes_remaining(), U64(4));
+    assert!(contract.claim_code_valid(code_hashes[0].clone()));
+
+    // anyone can claim for the receiver without a deposit
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(0)
+        .predecessor_account_id(accounts(4))
+        .build());
+    assert_eq!(contract.nft_claim("first secret".to_string(), accounts(3)), "0".to_string());
+    assert_eq!(contract.nft_owners_of(vec!["0".to_string()]), vec![Some(accounts(3))]);
+    assert!(!contract.claim_code_valid(code_hashes[0].clone()));
+
+    // the owner gets back the storage of the code and the unused part of the reserve
+    let owner_refunds = get_created_receipts().into_iter().filter(|receipt| receipt.receiver_id == accounts(1)).count();
+    assert_eq!(owner_refunds, 2);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(1)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.revoke_claim_codes(vec![code_hashes[1].clone()]);
+    assert_eq!(contract.claim_codes_remaining(), U64(2));
+
+    // the codes expire
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .block_timestamp(1680000100000000000)
+        .build());
+    assert!(!contract.claim_code_valid(code_hashes[2].clone()));
+    assert!(contract.claim_code_valid(code_hashes[3].clone()));
+}
+
+#[test]
+#[should_panic(expected = "Invalid claim code")]
+fn test_nft_claim_twice() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.register_claim_codes(vec![(Base64VecU8(env::sha256(b"secret")), None)]);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(0)
+        .predecessor_account_id(accounts(3))
+        .build());
+    contract.nft_claim("secret".to_string(), accounts(3));
+    contract.nft_claim("secret".to_string(), accounts(4));
+}
+
+#[test]
+fn test_claim_drop() {
+    let mut context = get_context(accounts(2));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let public_key: near_sdk::PublicKey = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap();
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(3 * MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.add_drop_keys(vec![public_key.clone()], Some(U128(MINT_STORAGE_COST)));
+    assert_eq!(contract.get_drop(public_key.clone()), Some(U128(MINT_STORAGE_COST)));
+
+    // the ephemeral key signs the call to the contract itself
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(0)
+        .predecessor_account_id(env::current_account_id())
+        .signer_account_pk(public_key.clone())
+        .build());
+    assert_eq!(contract.claim_drop(accounts(3)), "0".to_string());
+    assert_eq!(contract.nft_owners_of(vec!["0".to_string()]), vec![Some(accounts(3))]);
+    assert_eq!(contract.get_drop(public_key), None);
+}
+
+#[test]
+fn test_nft_mint_with_referrer() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context.attached_deposit(1).predecessor_account_id(accounts(1)).build());
+    contract.set_referral_bps(1000);
+
+    for buyer_index in [0, 3, 4] {
+        let referrer_id = if buyer_index == 4 { accounts(3) } else { accounts(5) };
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(buyer_index))
+            .block_timestamp(1680000000000000000)
+            .build());
+        contract.nft_mint(accounts(buyer_index), Some(referrer_id));
+    }
+
+    testing_env!(context.is_view(true).attached_deposit(0).build());
+    let stats = contract.referral_stats_of(accounts(5));
+    assert_eq!(stats.minted, 2);
+    assert_eq!(stats.earned, U128(2 * MINT_PRICE.0 / 10));
+
+    let leaderboard = contract.referral_leaderboard(None, None);
+    assert_eq!(leaderboard.len(), 2);
+    assert_eq!(leaderboard[0].referrer_id, accounts(5));
+    assert_eq!(leaderboard[1].referrer_id, accounts(3));
+    assert_eq!(leaderboard[1].stats.earned, U128(MINT_PRICE.0 / 10));
+    assert_eq!(contract.referral_leaderboard(Some(U128(1)), Some(1)).len(), 1);
+
+    // all the referrers in the order of their first referral
+    let referrers = contract.referrers(None, Some(5));
+    assert_eq!(referrers.len(), 2);
+    assert_eq!(referrers[1].referrer_id, accounts(3));
+}
+
+#[test]
+#[should_panic(expected = "Referrer must not be the buyer")]
+fn test_nft_mint_self_referral() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(3), Some(accounts(0)));
+}
+
+#[test]
+fn test_nft_mint_holder_price_tier() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let holder_price = U128(MINT_PRICE.0 / 2);
+    testing_env!(context.attached_deposit(1).predecessor_account_id(accounts(1)).build());
+    contract.set_price_tiers(vec![PriceTier { min_tokens: 1, price: holder_price }], None);
+    assert_eq!(contract.get_mint_price_for(accounts(0)), MINT_PRICE);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_PRICE.0 + MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(0))
+        .block_timestamp(1680000000000000000)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    assert_eq!(contract.get_mint_price_for(accounts(0)), holder_price);
+
+    // the holder pays the tiered price only
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(holder_price.0 + MINT_STORAGE_COST)
+        .build());
+    contract.nft_mint(accounts(0), None);
+    assert_eq!(contract.nft_supply_for_owner(accounts(0)), U128(2));
+}
+
+#[test]
+fn test_nft_resolve_partner_mint() {
+    use crate::pricing::PartnerMintResolver;
+    use near_sdk::{PromiseResult, RuntimeFeesConfig, VMConfig};
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let partner_price = U128(MINT_PRICE.0 / 5);
+    testing_env!(context.attached_deposit(1).predecessor_account_id(accounts(1)).build());
+    contract.set_price_tiers(vec![PriceTier { min_tokens: 3, price: partner_price }], Some(accounts(5)));
+
+    // the buyer holds 3 partner tokens
+    testing_env!(
+        context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(0)
+            .predecessor_account_id(env::current_account_id())
+            .block_timestamp(1680000000000000000)
+            .build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(b"\"3\"".to_vec())],
+    );
+    let deposit = U128(partner_price.0 + MINT_STORAGE_COST);
+    assert_eq!(contract.nft_resolve_partner_mint(accounts(0), accounts(4), None, deposit), Some("0".to_string()));
+    // the buyer is recorded as the minter, not the contract resolving the callback
+    assert_eq!(contract.nft_history("0".to_string(), None, None)[0].authorized_id, Some(accounts(0)));
+
+    // without partner tokens the deposit doesn't cover the full price and is refunded
+    testing_env!(
+        context.storage_usage(env::storage_usage()).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Failed],
+    );
+    assert_eq!(contract.nft_resolve_partner_mint(accounts(3), accounts(3), None, deposit), None);
+    assert_eq!(contract.nft_total_supply(), U128(1));
+
+    // the deposit covers the price but not the storage of the token, it is refunded without a panic
+    testing_env!(
+        context.storage_usage(env::storage_usage()).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(b"\"3\"".to_vec())],
+    );
+    assert_eq!(contract.nft_resolve_partner_mint(accounts(0), accounts(0), None, U128(partner_price.0 + 1)), None);
+    assert_eq!(contract.nft_total_supply(), U128(1));
+}
+
+fn sample_trait_table() -> Vec<TraitCategory> {
+    vec![
+        TraitCategory {
+            name: "Background".to_string(),
+            options: vec![
+                TraitOption { value: "Blue".to_string(), weight: 0 },
+                TraitOption { value: "Gold".to_string(), weight: 1 },
+            ],
+        },
+        TraitCategory {
+            name: "Hat".to_string(),
+            options: vec![TraitOption { value: "Cap".to_string(), weight: 5 }],
+        },
+    ]
+}
+
+#[test]
+fn test_nft_traits() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(sample_trait_table());
+    assert_eq!(contract.get_trait_table().len(), 2);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context.is_view(true).attached_deposit(0).build());
+    let traits = contract.nft_traits("0".to_string());
+    assert_eq!(traits[0], TokenTrait { trait_type: "Background".to_string(), value: "Gold".to_string() });
+    assert_eq!(traits[1], TokenTrait { trait_type: "Hat".to_string(), value: "Cap".to_string() });
+
+    let tokens = contract.nft_tokens_by_ids(vec!["0".to_string()]);
+    assert_eq!(
+        tokens[0].as_ref().unwrap().metadata.extra,
+        Some(r#"{"attributes":[{"trait_type":"Background","value":"Gold"},{"trait_type":"Hat","value":"Cap"}]}"#.to_string())
+    );
+}
+
+#[test]
+#[should_panic(expected = "Traits can't change after the first mint")]
+fn test_set_trait_table_after_mint() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_mint(accounts(0), None);
+
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.set_trait_table(sample_trait_table());
+}
+
+#[test]
+#[should_panic(expected = "Trait category Hat is duplicated")]
+fn test_set_trait_table_with_duplicate_category() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let mut categories = sample_trait_table();
+    categories.push(categories[1].clone());
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(categories);
+}
+
+#[test]
+#[should_panic(expected = "Trait category Background has the option Gold twice")]
+fn test_set_trait_table_with_duplicate_option() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let mut categories = sample_trait_table();
+    categories[0].options.push(TraitOption { value: "Gold".to_string(), weight: 3 });
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(categories);
+}
+
+#[test]
+#[should_panic(expected = "Traits can't change while claim codes are registered")]
+fn test_set_trait_table_with_claim_codes() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.register_claim_codes(vec![(Base64VecU8(env::sha256("secret".as_bytes())), None)]);
+
+    contract.set_trait_table(sample_trait_table());
+}
+
+#[test]
+fn test_nft_tokens_with_trait() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(sample_trait_table());
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0), accounts(3), accounts(4)]);
+
+    testing_env!(context.is_view(true).attached_deposit(0).build());
+    let tokens = contract.nft_tokens_with_trait("Background".to_string(), "Gold".to_string(), Some(U128(1)), Some(5));
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[0].token_id, "1".to_string());
+    assert!(contract.nft_tokens_with_trait("Background".to_string(), "Blue".to_string(), None, None).is_empty());
+    assert!(contract.nft_tokens_with_trait("Shoes".to_string(), "Red".to_string(), None, None).is_empty());
+
+    let counts = contract.nft_trait_counts("Background".to_string());
+    assert_eq!(counts.len(), 2);
+    assert_eq!((counts[0].value.as_str(), counts[0].count), ("Blue", U64(0)));
+    assert_eq!((counts[1].value.as_str(), counts[1].count), ("Gold", U64(3)));
+
+    // every token has the only possible traits: 2 categories of 3 / 3
+    assert_eq!(contract.nft_rarity_score("0".to_string()), Some(U64(2000)));
+    assert_eq!(contract.nft_rarity_score("7".to_string()), None);
+}
+
+#[test]
+fn test_svg_media() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(sample_trait_table());
+    contract.set_trait_layer("Background".to_string(), "Gold".to_string(), Some("<rect fill=\"#fc0\"/>".to_string()));
+    contract.set_trait_layer("Hat".to_string(), "Cap".to_string(), Some("<circle r='5'/>".to_string()));
+    assert_eq!(contract.get_trait_layer("Hat".to_string(), "Cap".to_string()), Some("<circle r='5'/>".to_string()));
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.nft_airdrop(vec![accounts(0)]);
+
+    testing_env!(context.attached_deposit(1).predecessor_account_id(accounts(1)).build());
+    contract.set_svg_media(Some("0 0 10 10".to_string()));
+
+    testing_env!(context.is_view(true).attached_deposit(0).build());
+    let tokens = contract.nft_tokens_by_ids(vec!["0".to_string()]);
+    assert_eq!(
+        tokens[0].as_ref().unwrap().metadata.media,
+        Some("data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 10 10'%3E%3Crect fill=%22%23fc0%22/%3E%3Ccircle r='5'/%3E%3C/svg%3E".to_string())
+    );
+}
+
+#[test]
+fn test_nft_resolve_drop() {
+    use crate::drop::DropResolver;
+    use near_sdk::{PromiseResult, RuntimeFeesConfig, VMConfig};
+    let mut context = get_context(accounts(2));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let public_key: near_sdk::PublicKey = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap();
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(3 * MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.add_drop_keys(vec![public_key.clone()], Some(U128(MINT_STORAGE_COST)));
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(0)
+        .predecessor_account_id(env::current_account_id())
+        .signer_account_pk(public_key.clone())
+        .build());
+    contract.create_account_and_claim_drop("runner.testnet".parse().unwrap(), public_key.clone());
+    assert_eq!(contract.get_drop(public_key.clone()), None);
+    let drop = NftDrop { account_deposit: U128(MINT_STORAGE_COST), allowance: U128(0), mint_deposit: U128(MINT_STORAGE_COST) };
+
+    // the account name was taken, the drop can be claimed again
+    testing_env!(
+        context.storage_usage(env::storage_usage()).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(b"false".to_vec())],
+    );
+    assert!(!contract.nft_resolve_drop("runner.testnet".parse().unwrap(), public_key.clone(), drop.clone()));
+    assert_eq!(contract.get_drop(public_key.clone()), Some(U128(MINT_STORAGE_COST)));
+    assert_eq!(contract.nft_total_supply(), U128(0));
+
+    testing_env!(context.storage_usage(env::storage_usage()).build());
+    contract.create_account_and_claim_drop("runner2.testnet".parse().unwrap(), public_key.clone());
+    testing_env!(
+        context.storage_usage(env::storage_usage()).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(b"true".to_vec())],
+    );
+    assert!(contract.nft_resolve_drop("runner2.testnet".parse().unwrap(), public_key.clone(), drop.clone()));
+    assert_eq!(contract.nft_owners_of(vec!["0".to_string()]), vec![Some("runner2.testnet".parse().unwrap())]);
+    assert_eq!(contract.nft_history("0".to_string(), None, None)[0].authorized_id, Some(accounts(2)));
+    assert_eq!(contract.get_drop(public_key), None);
+}
+
+#[test]
+fn test_nft_resolve_drop_with_stale_reserve() {
+    use crate::drop::DropResolver;
+    use near_sdk::mock::VmAction;
+    use near_sdk::{PromiseResult, RuntimeFeesConfig, VMConfig};
+    let mut context = get_context(accounts(2));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1),accounts(2), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let public_key: near_sdk::PublicKey = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap();
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(3 * MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.add_drop_keys(vec![public_key.clone()], Some(U128(MINT_STORAGE_COST)));
+    let mint_deposit = contract.internal_mint_storage_deposit();
+
+    // the trait table grows after the drop was funded
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(sample_trait_table());
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(0)
+        .predecessor_account_id(env::current_account_id())
+        .signer_account_pk(public_key.clone())
+        .build());
+    contract.create_account_and_claim_drop("runner.testnet".parse().unwrap(), public_key.clone());
+    let drop = NftDrop { account_deposit: U128(MINT_STORAGE_COST), allowance: U128(0), mint_deposit: U128(mint_deposit) };
+
+    // the account is created but the reserve doesn't cover the token anymore, it goes back to the treasury
+    testing_env!(
+        context.storage_usage(env::storage_usage()).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![PromiseResult::Successful(b"true".to_vec())],
+    );
+    assert!(contract.nft_resolve_drop("runner.testnet".parse().unwrap(), public_key.clone(), drop));
+    assert_eq!(contract.nft_total_supply(), U128(0));
+    assert!(get_created_receipts().iter().any(|receipt| receipt.receiver_id == accounts(2)
+        && receipt.actions == vec![VmAction::Transfer { deposit: mint_deposit }]));
+    assert_eq!(contract.get_drop(public_key), None);
+}
+
+#[test]
+#[should_panic(expected = "Drop has no deposit for a new account")]
+fn test_create_account_drop_without_deposit() {
+    let mut context = get_context(accounts(2));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    let public_key: near_sdk::PublicKey = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap();
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(2 * MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.add_drop_keys(vec![public_key.clone()], None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(0)
+        .predecessor_account_id(env::current_account_id())
+        .signer_account_pk(public_key.clone())
+        .build());
+    contract.create_account_and_claim_drop("runner.testnet".parse().unwrap(), public_key);
+}
+
+#[test]
+fn test_nft_claim_with_traits() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new_default_meta(accounts(1).into(),accounts(2).into(), MAX_SUPPLY, DEFAULT_BASE_URI.to_string(), MINT_PRICE, MINT_START, MINT_END, None);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(MINT_STORAGE_COST)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_trait_table(sample_trait_table());
+
+    // the reserve of every code covers the token and its 2 traits
+    testing_env!(context.storage_usage(env::storage_usage()).build());
+    contract.register_claim_codes(vec![(Base64VecU8(env::sha256(b"secret")), None)]);
+
+    testing_env!(context
+        .storage_usage(env::storage_usage())
+        .attached_deposit(0)
+        .predecessor_account_id(accounts(3))
+        .build());
+    assert_eq!(contract.nft_claim("secret".to_string(), accounts(3)), "0".to_string());
+    assert_eq!(contract.nft_traits("0".to_string()).len(), 2);
+}
+
+fn contract_metadata() -> NFTContractMetadata {
+    NFTContractMetadata {
+        spec: "nft-1.0.0".to_string(),
+        name: "Chubby Runners".to_string(),
+        symbol: "RUNNER".to_string(),
+        icon: None,
+        base_uri: Some("https://gateway.purecube.io/runner-testnet".to_string()),
+        reference: None,
+        reference_hash: None,
+    }
+}
+
+fn token_metadata() -> TokenMetadata {
+    TokenMetadata {
+        title: Some("Chubby Runner #0".to_string()),
+        description: None,
+        media: Some("img/0.png".to_string()),
+        media_hash: Some(Base64VecU8(vec![0; 32])),
+        copies: Some(1u64),
+        issued_at: None,
+        expires_at: None,
+        starts_at: None,
+        updated_at: None,
+        extra: None,
+        reference: None,
+        reference_hash: None,
+    }
+}
+
+#[test]
+fn test_valid_metadata() {
+    contract_metadata().assert_valid();
+    token_metadata().assert_valid();
+
+    let mut metadata = contract_metadata();
+    metadata.reference = Some("data/contract.json".to_string());
+    metadata.reference_hash = Some(Base64VecU8(vec![0; 32]));
+    metadata.assert_valid();
+
+    // the generated media and reference don't have hashes
+    let mut metadata = token_metadata();
+    metadata.media_hash = None;
+    metadata.reference = Some("data/0.json".to_string());
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Spec must start with nft-")]
+fn test_contract_spec_prefix() {
+    let mut metadata = contract_metadata();
+    metadata.spec = "1.0.0".to_string();
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Base URI must be less then 100 chars")]
+fn test_contract_base_uri_len() {
+    let mut metadata = contract_metadata();
+    metadata.base_uri = Some("a".repeat(101));
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Reference and reference hash must be present together")]
+fn test_contract_reference_without_hash() {
+    let mut metadata = contract_metadata();
+    metadata.reference = Some("data/contract.json".to_string());
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Reference and reference hash must be present together")]
+fn test_contract_hash_without_reference() {
+    let mut metadata = contract_metadata();
+    metadata.reference_hash = Some(Base64VecU8(vec![0; 32]));
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Reference hash has to be 32 bytes")]
+fn test_contract_reference_hash_len() {
+    let mut metadata = contract_metadata();
+    metadata.reference = Some("data/contract.json".to_string());
+    metadata.reference_hash = Some(Base64VecU8(vec![0; 31]));
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Media hash requires media")]
+fn test_token_hash_without_media() {
+    let mut metadata = token_metadata();
+    metadata.media = None;
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Media hash has to be 32 bytes")]
+fn test_token_media_hash_len() {
+    let mut metadata = token_metadata();
+    metadata.media_hash = Some(Base64VecU8(vec![0; 33]));
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Reference hash requires reference")]
+fn test_token_hash_without_reference() {
+    let mut metadata = token_metadata();
+    metadata.reference_hash = Some(Base64VecU8(vec![0; 32]));
+    metadata.assert_valid();
+}
+
+#[test]
+#[should_panic(expected = "Reference hash has to be 32 bytes")]
+fn test_token_reference_hash_len() {
+    let mut metadata = token_metadata();
+    metadata.reference = Some("data/0.json".to_string());
+    metadata.reference_hash = Some(Base64VecU8(vec![0; 16]));
+    metadata.assert_valid();
+}
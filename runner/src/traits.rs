@@ -0,0 +1,288 @@
+use crate::*;
+
+//max number of trait categories, every category takes one byte per token
+const MAX_TRAIT_CATEGORIES: usize = 16;
+//max number of options in a category, the rolled option index is stored in one byte
+const MAX_TRAIT_OPTIONS: usize = 256;
+
+//a value of the trait and its chance to be rolled relative to the other options of the category
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TraitOption {
+    pub value: String,
+    pub weight: u32,
+}
+
+//a category of traits, every token gets one of its options
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TraitCategory {
+    pub name: String,
+    pub options: Vec<TraitOption>,
+}
+
+//a rolled trait of the token, same as the attributes of the off-chain metadata
+#[derive(BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenTrait {
+    pub trait_type: String,
+    pub value: String,
+}
+
+//number of tokens with the option of the trait category
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TraitCount {
+    pub value: String,
+    pub count: U64,
+}
+
+//the traits stored in the extra field of the token metadata
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct TraitsExtra {
+    attributes: Vec<TokenTrait>,
+}
+
+#[near_bindgen]
+impl Contract {
+    //change the trait categories rolled at mint. They can't change after the first mint or once the metadata is frozen
+    #[payable]
+    pub fn set_trait_table(&mut self, categories: Vec<TraitCategory>) {
+        assert_at_least_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        self.assert_metadata_not_frozen();
+        assert_eq!(self.nft_total_supply().0, 0, "Traits can't change after the first mint");
+        //the registered claim codes reserve the storage of a token with the current trait table
+        assert_eq!(self.claim_codes_count, 0, "Traits can't change while claim codes are registered");
+        assert!(
+            categories.len() <= MAX_TRAIT_CATEGORIES,
+            "Cannot set more than {} trait categories",
+            MAX_TRAIT_CATEGORIES
+        );
+        for (category_index, category) in categories.iter().enumerate() {
+            //the traits are found by name and value so they must be unique
+            assert!(
+                categories[..category_index].iter().all(|other| other.name != category.name),
+                "Trait category {} is duplicated",
+                category.name
+            );
+            for (option_index, option) in category.options.iter().enumerate() {
+                assert!(
+                    category.options[..option_index].iter().all(|other| other.value != option.value),
+                    "Trait category {} has the option {} twice",
+                    category.name,
+                    option.value
+                );
+            }
+            assert!(
+                !category.options.is_empty() && category.options.len() <= MAX_TRAIT_OPTIONS,
+                "Trait category {} must have 1 to {} options",
+                category.name,
+                MAX_TRAIT_OPTIONS
+            );
+            assert!(
+                category.options.iter().any(|option| option.weight > 0),
+                "Trait category {} must have an option with a positive weight",
+                category.name
+            );
+        }
+
+        let initial_storage_usage = env::storage_usage();
+
+        self.trait_table.set(&categories);
+
+        //pay for the bigger table with the attached deposit, a smaller one refunds the released storage
+        let final_storage_usage = env::storage_usage();
+        refund_deposit(final_storage_usage.saturating_sub(initial_storage_usage));
+        if initial_storage_usage > final_storage_usage {
+            refund_released_storage(self.owner_id.clone(), initial_storage_usage - final_storage_usage);
+        }
+    }
+
+    //get the trait categories rolled at mint
+    pub fn get_trait_table(&self) -> Vec<TraitCategory> {
+        self.trait_table.get().unwrap_or_default()
+    }
+
+    //get the traits rolled for the token, nothing if the token has no traits
+    pub fn nft_traits(&self, token_id: TokenId) -> Vec<TokenTrait> {
+        self.internal_token_traits(&token_id)
+    }
+
+    //get the tokens with the value of the trait category using pagination
+    pub fn nft_tokens_with_trait(
+        &self,
+        category: String,
+        value: String,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        let tokens = if let Some(tokens) = self.internal_tokens_with_trait(&category, &value) {
+            tokens
+        } else {
+            return vec![];
+        };
+
+        let keys = tokens.as_vector();
+        page_range(from_index, limit, keys.len())
+            .map(|index| self.nft_token(keys.get(index).unwrap()).unwrap())
+            .collect()
+    }
+
+    //get the number of tokens with every option of the trait category
+    pub fn nft_trait_counts(&self, category: String) -> Vec<TraitCount> {
+        let categories = self.trait_table.get().unwrap_or_default();
+        let category_index = if let Some(index) = categories.iter().position(|item| item.name == category) {
+            index
+        } else {
+            return vec![];
+        };
+
+        categories[category_index]
+            .options
+            .iter()
+            .enumerate()
+            .map(|(option_index, option)| TraitCount {
+                value: option.value.clone(),
+                count: U64(self.internal_trait_count(category_index as u8, option_index as u8)),
+            })
+            .collect()
+    }
+
+    /*
+        get the rarity score of the token, the sum of total supply / tokens with the same option over all the trait
+        categories in thousandths. The rarer the traits, the higher the score. Nothing if the token has no traits
+    */
+    pub fn nft_rarity_score(&self, token_id: TokenId) -> Option<U64> {
+        let traits = self.token_traits.get(&token_id)?;
+        //the trait table can't change after the first mint so every token has traits
+        let total = self.nft_total_supply().0 as u64;
+
+        let score = traits
+            .iter()
+            .enumerate()
+            .map(|(category_index, option_index)| {
+                total * 1000 / self.internal_trait_count(category_index as u8, *option_index)
+            })
+            .sum();
+        Some(U64(score))
+    }
+}
+
+impl Contract {
+    /*
+        roll one option of every trait category for the token. The roll uses the random seed of the block
+        mixed with the token ID and the category index, so every token and category gets a different number.
+        The category of the pinned trait isn't rolled, the token gets the pinned option
+    */
+    pub(crate) fn internal_roll_traits(&mut self, token_id: &TokenId, pinned_trait: Option<(u8, u8)>) {
+        let categories = self.trait_table.get().unwrap_or_default();
+        if categories.is_empty() {
+            return;
+        }
+
+        let random_seed = env::random_seed();
+        let traits: Vec<u8> = categories
+            .iter()
+            .enumerate()
+            .map(|(category_index, category)| {
+                if let Some((pinned_category, pinned_option)) = pinned_trait {
+                    if pinned_category as usize == category_index {
+                        return pinned_option;
+                    }
+                }
+                let hash = env::sha256(&[random_seed.as_slice(), token_id.as_bytes(), &[category_index as u8]].concat());
+                let total_weight: u64 = category.options.iter().map(|option| option.weight as u64).sum();
+                let mut roll = u64::from_le_bytes(hash[..8].try_into().unwrap()) % total_weight;
+
+                //pick the option whose weight range contains the roll
+                let mut option_index = 0;
+                for (index, option) in category.options.iter().enumerate() {
+                    if roll < option.weight as u64 {
+                        option_index = index;
+                        break;
+                    }
+                    roll -= option.weight as u64;
+                }
+                option_index as u8
+            })
+            .collect();
+
+        self.token_traits.insert(token_id, &traits);
+
+        //add the token to the set of tokens for every rolled option
+        for (category_index, option_index) in traits.into_iter().enumerate() {
+            let key = (category_index as u8, option_index);
+            let mut tokens_set = self.tokens_per_trait.get(&key).unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::TokensPerTraitInner {
+                        category_index: key.0,
+                        option_index: key.1,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+            tokens_set.insert(token_id);
+            self.tokens_per_trait.insert(&key, &tokens_set);
+        }
+    }
+
+    //get the category and option indexes of the value of the trait category, nothing if there is no such trait
+    pub(crate) fn internal_trait_key(&self, category: &str, value: &str) -> Option<(u8, u8)> {
+        let categories = self.trait_table.get()?;
+        let category_index = categories.iter().position(|item| item.name == category)?;
+        let option_index = categories[category_index]
+            .options
+            .iter()
+            .position(|option| option.value == value)?;
+        Some((category_index as u8, option_index as u8))
+    }
+
+    //get the set of tokens with the value of the trait category, nothing if there is no such trait
+    pub(crate) fn internal_tokens_with_trait(&self, category: &str, value: &str) -> Option<UnorderedSet<TokenId>> {
+        let key = self.internal_trait_key(category, value)?;
+        self.tokens_per_trait.get(&key)
+    }
+
+    //get the number of tokens with the option of the trait category
+    pub(crate) fn internal_trait_count(&self, category_index: u8, option_index: u8) -> u64 {
+        self.tokens_per_trait
+            .get(&(category_index, option_index))
+            .map(|tokens| tokens.len())
+            .unwrap_or(0)
+    }
+
+    //get the rolled traits of the token with the names from the trait table
+    pub(crate) fn internal_token_traits(&self, token_id: &TokenId) -> Vec<TokenTrait> {
+        let traits = if let Some(traits) = self.token_traits.get(token_id) {
+            traits
+        } else {
+            return vec![];
+        };
+        let categories = self.trait_table.get().unwrap_or_default();
+
+        traits
+            .iter()
+            .zip(categories.iter())
+            .map(|(option_index, category)| TokenTrait {
+                trait_type: category.name.clone(),
+                value: category.options[*option_index as usize].value.clone(),
+            })
+            .collect()
+    }
+
+    //get the traits of the token as the stringified JSON stored in the extra field of the metadata
+    pub(crate) fn internal_token_traits_extra(&self, token_id: &TokenId) -> Option<String> {
+        let attributes = self.internal_token_traits(token_id);
+        if attributes.is_empty() {
+            return None;
+        }
+        near_sdk::serde_json::to_string(&TraitsExtra { attributes }).ok()
+    }
+}
@@ -0,0 +1,113 @@
+use crate::*;
+use ed25519_dalek::Verifier;
+use near_sdk::CurveType;
+
+//a mint signed off-chain by the backend. The player redeems it with nft_mint_with_voucher
+#[derive(BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintVoucher {
+    //account which receives the token
+    pub receiver_id: AccountId,
+    //trait the token gets instead of rolling its category (the kind of runner), nothing rolls all the traits
+    pub token_type: Option<TokenTrait>,
+    //yoctoNEAR paid to the treasury for the mint, can be 0
+    pub price: U128,
+    //unique number of the voucher, every nonce can be redeemed once
+    pub nonce: U64,
+    //nanoseconds timestamp after which the voucher can't be redeemed
+    pub expires_at: U64,
+}
+
+impl MintVoucher {
+    //the signed message is the borsh serialized contract account ID followed by the voucher
+    //so the voucher can't be redeemed on another contract
+    pub fn message(&self, contract_id: &AccountId) -> Vec<u8> {
+        (contract_id, self).try_to_vec().unwrap()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //set the ed25519 key of the backend which signs the mint vouchers. No key disables the vouchers
+    #[payable]
+    pub fn set_voucher_signer(&mut self, public_key: Option<PublicKey>) {
+        assert_one_yocto();
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Predecessor must be contract owner."
+        );
+        if let Some(public_key) = public_key.as_ref() {
+            assert!(public_key.curve_type() == CurveType::ED25519, "Voucher signer must be an ed25519 key");
+        }
+
+        self.voucher_signer = public_key;
+    }
+
+    //get the key of the backend which signs the mint vouchers
+    pub fn get_voucher_signer(&self) -> Option<PublicKey> {
+        self.voucher_signer.clone()
+    }
+
+    //mint the token described by a voucher signed by the backend. The caller pays the voucher price and the storage
+    #[payable]
+    pub fn nft_mint_with_voucher(&mut self, voucher: MintVoucher, signature: Base64VecU8) {
+        let public_key = self.voucher_signer.as_ref().expect("Vouchers are disabled");
+        assert!(
+            voucher.expires_at.0 > env::block_timestamp(),
+            "Voucher expired {}, now {}",
+            voucher.expires_at.0,
+            env::block_timestamp()
+        );
+        assert!(!self.used_voucher_nonces.contains(&voucher.nonce.0), "Voucher is already used");
+
+        //make sure the voucher was signed by the backend
+        let public_key = ed25519_dalek::PublicKey::from_bytes(&public_key.as_bytes()[1..])
+            .expect("Invalid voucher signer");
+        let signature = ed25519_dalek::Signature::try_from(signature.0.as_slice()).expect("Invalid signature");
+        assert!(
+            public_key
+                .verify(&voucher.message(&env::current_account_id()), &signature)
+                .is_ok(),
+            "Invalid signature"
+        );
+
+        //the token type must be an option of the trait table
+        let pinned_trait = voucher.token_type.as_ref().map(|token_type| {
+            self.internal_trait_key(&token_type.trait_type, &token_type.value)
+                .expect("Unknown token type")
+        });
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        self.used_voucher_nonces.insert(&voucher.nonce.0);
+        self.internal_mint_next(voucher.receiver_id, env::predecessor_account_id(), voucher.price, pinned_trait);
+
+        //the caller pays for the storage with the reserve for the history and the price of the voucher
+        let required_cost = env::storage_byte_cost()
+            * Balance::from(env::storage_usage() - initial_storage_usage + HISTORY_STORAGE_RESERVE)
+            + voucher.price.0;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            required_cost <= attached_deposit,
+            "Must attach {} yoctoNEAR to cover storage and mint price",
+            required_cost,
+        );
+
+        if voucher.price.0 > 0 {
+            Promise::new(self.treasury_id.clone()).transfer(voucher.price.0);
+        }
+
+        //if the refund is greater than 1 yocto NEAR, we refund the predecessor that amount
+        let refund = attached_deposit - required_cost;
+        if refund > 1 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+
+    //check if the voucher with the nonce was already redeemed
+    pub fn voucher_used(&self, nonce: U64) -> bool {
+        self.used_voucher_nonces.contains(&nonce.0)
+    }
+}